@@ -0,0 +1,303 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::error::{Result, ZtlnError};
+
+/**
+A bibliographic source imported from a BibTeX file, notes cite it by its
+citekey.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Source {
+    pub citekey: String,
+    /// BibTeX entry type: `book`, `article`...
+    pub entry_type: String,
+    /// lowercase field names with their values, outer braces removed
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Source {
+    /**
+     * Parse the entries of a BibTeX file, `@comment`, `@string` and
+     * `@preamble` blocks are skipped. Like BibTeX, the text between entries
+     * is ignored: an `@` starts an entry only when followed by an entry type
+     * and `{` or `(`.
+     */
+    pub fn parse_bibtex(content: &str) -> Result<Vec<Source>> {
+        let mut parser = Parser { chars: content.chars().collect(), position: 0 };
+        let mut sources = Vec::new();
+        while parser.skip_to('@') {
+            parser.position += 1;
+            let start = parser.position;
+            let entry_type = parser.take_while(|c| c.is_alphanumeric()).to_lowercase();
+            parser.skip_whitespace();
+            let is_entry = entry_type.starts_with(char::is_alphabetic);
+            let close = match parser.peek() {
+                Some('{') if is_entry => '}',
+                Some('(') if is_entry => ')',
+                // not an entry, e.g. an email address in a comment
+                _ => {
+                    parser.position = start;
+                    continue;
+                },
+            };
+            if ["comment", "string", "preamble"].contains(&entry_type.as_str()) {
+                parser.skip_block(close)?;
+                continue;
+            }
+            parser.position += 1;
+            let citekey = parser.take_while(|c| c != ',' && c != close).trim().to_string();
+            if citekey.is_empty() || citekey.contains(char::is_whitespace) {
+                return Err(parser.error("expected a citekey"));
+            }
+            let mut fields = BTreeMap::new();
+            loop {
+                parser.skip_whitespace();
+                match parser.next() {
+                    Some(',') => continue,
+                    Some(c) if c == close => break,
+                    Some(_) => parser.position -= 1,
+                    None => return Err(parser.error(&format!("entry '{}' is not closed", citekey))),
+                }
+                let name = parser.take_while(|c| c != '=' && c != ',' && c != close).trim().to_lowercase();
+                if parser.next() != Some('=') || name.is_empty() {
+                    return Err(parser.error(&format!("expected 'field = value' in entry '{}'", citekey)));
+                }
+                parser.skip_whitespace();
+                let value = match parser.peek() {
+                    Some('{') => parser.read_braced()?,
+                    Some('"') => parser.read_quoted()?,
+                    _ => parser.take_while(|c| c != ',' && c != close).trim().to_string(),
+                };
+                fields.insert(name, value.split_whitespace().collect::<Vec<&str>>().join(" "));
+            }
+            sources.push(Source { citekey, entry_type, fields });
+        }
+
+        Ok(sources)
+    }
+
+    /**
+     * The entry formatted as BibTeX.
+     */
+    pub fn to_bibtex(&self) -> String {
+        let mut bibtex = format!("@{}{{{},\n", self.entry_type, self.citekey);
+        for (name, value) in &self.fields {
+            bibtex.push_str(&format!("  {} = {{{}}},\n", name, value));
+        }
+        bibtex.push_str("}\n");
+
+        bibtex
+    }
+
+    /**
+     * The last names of the authors (or editors): `Ahrens`, `Ahrens and
+     * Doe` or `Ahrens et al.`
+     */
+    pub fn authors(&self) -> Option<String> {
+        let names = self.fields.get("author").or_else(|| self.fields.get("editor"))?;
+        let last_names: Vec<&str> = names.split(" and ")
+            .map(|name| match name.split_once(',') {
+                Some((last_name, _)) => last_name.trim(),
+                None => name.split_whitespace().last().unwrap_or(""),
+            })
+            .collect();
+
+        Some(match last_names.as_slice() {
+            [one] => one.to_string(),
+            [first, second] => format!("{} and {}", first, second),
+            [first, ..] => format!("{} et al.", first),
+            [] => return None,
+        })
+    }
+
+    /**
+     * Citation of the source, e.g. `Ahrens (2017), How to Take Smart Notes,
+     * p. 42`.
+     */
+    pub fn citation(&self, page: Option<&str>) -> String {
+        let mut citation = self.authors().unwrap_or_else(|| self.citekey.clone());
+        if let Some(year) = self.fields.get("year") {
+            citation.push_str(&format!(" ({})", year));
+        }
+        if let Some(title) = self.fields.get("title") {
+            citation.push_str(&format!(", {}", title.replace(['{', '}'], "")));
+        }
+        if let Some(page) = page {
+            citation.push_str(&format!(", p. {}", page));
+        }
+
+        citation
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn skip_to(&mut self, target: char) -> bool {
+        while let Some(c) = self.peek() {
+            if c == target {
+                return true;
+            }
+            self.position += 1;
+        }
+        false
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /**
+     * A `{...}` value without its outer braces, inner braces are kept.
+     */
+    fn read_braced(&mut self) -> Result<String> {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(c) = self.next() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 1 => return Ok(self.chars[start + 1..self.position - 1].iter().collect()),
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        Err(self.error_at(start, "unbalanced braces"))
+    }
+
+    /**
+     * Skip a block from its opening delimiter to the given closing one, the
+     * braces and quoted values inside it are skipped as a whole.
+     */
+    fn skip_block(&mut self, close: char) -> Result<()> {
+        let start = self.position;
+        self.position += 1;
+        loop {
+            match self.peek() {
+                Some('{') => {
+                    self.read_braced()?;
+                },
+                Some('"') => {
+                    self.read_quoted()?;
+                },
+                Some(c) if c == close => {
+                    self.position += 1;
+                    return Ok(());
+                },
+                Some(_) => self.position += 1,
+                None => return Err(self.error_at(start, &format!("expected '{}' to close the block", close))),
+            }
+        }
+    }
+
+    fn read_quoted(&mut self) -> Result<String> {
+        let start = self.position;
+        self.position += 1;
+        let mut depth = 0;
+        while let Some(c) = self.next() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => return Ok(self.chars[start + 1..self.position - 1].iter().collect()),
+                _ => (),
+            }
+        }
+        Err(self.error_at(start, "unterminated quoted value"))
+    }
+
+    fn error(&self, message: &str) -> ZtlnError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at(&self, position: usize, message: &str) -> ZtlnError {
+        let line = self.chars[..position.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+        ZtlnError::ParserError("bibtex".to_string(), Some(format!("line {}: {}", line, message)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn parse_bibtex() {
+        let content = r#"
+% my sources
+@comment{ ignored {entry} }
+@Book{ahrens2017,
+  author    = {Ahrens, S{\"o}nke},
+  title     = {How to Take Smart Notes: One Simple Technique},
+  year      = 2017,
+  publisher = "CreateSpace {Independent}",
+}
+@article(doe2020, author = "John Doe and Jane Roe and Max Mustermann", title={Zettels})
+"#;
+        let sources = Source::parse_bibtex(content).unwrap();
+        assert_eq!(2, sources.len());
+        let book = &sources[0];
+        assert_eq!(("ahrens2017", "book"), (book.citekey.as_str(), book.entry_type.as_str()));
+        assert_eq!(Some(&"2017".to_string()), book.fields.get("year"));
+        assert_eq!(Some(&"CreateSpace {Independent}".to_string()), book.fields.get("publisher"));
+        assert_eq!("Ahrens (2017), How to Take Smart Notes: One Simple Technique, p. 42", book.citation(Some("42")));
+        assert_eq!("Doe et al., Zettels", sources[1].citation(None));
+        assert_eq!(sources[..1], Source::parse_bibtex(&book.to_bibtex()).unwrap()[..]);
+
+        assert_eq!(ErrorKind::Parse, Source::parse_bibtex("@book{key, title = {unbalanced}").unwrap_err().kind());
+        assert_eq!(ErrorKind::Parse, Source::parse_bibtex("@book{key, title}").unwrap_err().kind());
+        assert_eq!(ErrorKind::Parse, Source::parse_bibtex("@book{, title = {no key}}").unwrap_err().kind());
+        assert!(Source::parse_bibtex("no entry").unwrap().is_empty());
+    }
+
+    #[test]
+    fn text_between_entries() {
+        let content = r#"
+% maintained by jane@example.org, write to @jane
+Entries of the reading list: @ the end.
+@misc{draft, title = {Draft}}
+Any text (even with braces {}) @2021 is ignored
+@Online (site, url = {https://example.org/@home})
+"#;
+        let sources = Source::parse_bibtex(content).unwrap();
+        assert_eq!(vec!["draft", "site"], sources.iter().map(|source| source.citekey.as_str()).collect::<Vec<&str>>());
+        assert_eq!(Some(&"https://example.org/@home".to_string()), sources[1].fields.get("url"));
+        assert_eq!(ErrorKind::Parse, Source::parse_bibtex("me@example.org\n@book{key, title}").unwrap_err().kind());
+    }
+
+    #[test]
+    fn parenthesized_blocks() {
+        let content = r#"
+@string(jan = "January")
+@comment(skipped {entry} "with a ) inside")
+@preamble("\newcommand{\noop}[1]{}")
+@book{k1, title={T}}
+@STRING (feb = {February (2nd month)})
+@misc{k2, title={U}}
+"#;
+        let sources = Source::parse_bibtex(content).unwrap();
+        assert_eq!(vec!["k1", "k2"], sources.iter().map(|source| source.citekey.as_str()).collect::<Vec<&str>>());
+        assert_eq!(ErrorKind::Parse, Source::parse_bibtex("@string(jan = \"January\"\n@book{k1, title={T}}").unwrap_err().kind());
+    }
+}
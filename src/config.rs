@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::error::{IoContext, Result, ZtlnError};
+
+/**
+Name of the configuration file in the organization directory.
+ */
+pub const CONFIG_FILE: &str = "config";
+
+/**
+Global options of the command line taking a value, they are skipped when
+looking for the command name.
+ */
+const VALUE_OPTIONS: [&str; 2] = ["--base-dir", "--format"];
+
+/**
+Preferences read from the user configuration file
+(`$XDG_CONFIG_HOME/ztln/config.toml`, `~/.config/ztln/config.toml` by default)
+and from the `config` file of the organization, which takes precedence. Both
+are TOML files, every key is optional.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// organization used when neither `--base-dir` nor `ZTLN_BASE_DIR` is given
+    pub base_dir: Option<String>,
+    /// command launched to write notes, `$EDITOR` is used otherwise
+    pub editor: Option<String>,
+    /// name of the first path of new topics
+    pub default_path: Option<String>,
+    /// output format when `--format` is not given: `text` or `json`
+    pub format: Option<String>,
+    /// command displaying long outputs when printing on a terminal
+    pub pager: Option<String>,
+    /// `auto` (on a terminal), `always` or `never`
+    pub color: Option<String>,
+    /// replace the locations of wiki-links by the UUIDs they lead to
+    pub rewrite_links: Option<bool>,
+    /// note template files by name, relative to the configuration file
+    pub templates: HashMap<String, PathBuf>,
+    /// commands replacing an unknown command name, e.g. `log = "path log"`
+    pub aliases: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn parse(content: &str, origin: &Path) -> Result<Self> {
+        let config: Config = toml::from_str(content)
+            .map_err(|e| ZtlnError::ConfigError(origin.to_path_buf(), e.message().to_string()))?;
+        let check = |key: &str, value: &Option<String>, allowed: &[&str]| match value {
+            Some(value) if !allowed.contains(&value.as_str()) => Err(ZtlnError::ConfigError(
+                origin.to_path_buf(),
+                format!("'{}' must be one of {}, not '{}'", key, allowed.join(", "), value),
+            )),
+            _ => Ok(()),
+        };
+        check("format", &config.format, &["text", "json"])?;
+        check("color", &config.color, &["auto", "always", "never"])?;
+
+        Ok(config)
+    }
+
+    /**
+     * Read a configuration file, a missing file is an empty configuration.
+     * The relative paths of the templates are resolved from the directory of
+     * the file.
+     */
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Config::default());
+        }
+        let content = std::fs::read_to_string(path).context("read", path)?;
+        let mut config = Config::parse(&content, path)?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = std::path::absolute(dir).context("read", dir)?;
+        for file in config.templates.values_mut() {
+            *file = dir.join(&file);
+        }
+
+        Ok(config)
+    }
+
+    /**
+     * The user configuration file, if a home directory is known.
+     */
+    pub fn user_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("ztln").join("config.toml"))
+    }
+
+    /**
+     * Load the user configuration then the configuration of the organization
+     * (the given one or the one of the user configuration). The `base_dir`
+     * of the result is the organization to use, if any.
+     */
+    pub fn load(base_dir: Option<&str>) -> Result<Self> {
+        let user = match Config::user_path() {
+            Some(path) => Config::read(&path)?,
+            None => Config::default(),
+        };
+        let base_dir = base_dir.map(|dir| dir.to_string()).or_else(|| user.base_dir.clone());
+        let organization = match base_dir.as_ref() {
+            Some(dir) => Config::read(&Path::new(dir).join(CONFIG_FILE))?,
+            None => Config::default(),
+        };
+        let mut config = user.merge(organization);
+        config.base_dir = base_dir;
+
+        Ok(config)
+    }
+
+    /**
+     * The values of the other configuration take precedence, templates and
+     * aliases are merged.
+     */
+    pub fn merge(mut self, other: Config) -> Self {
+        self.base_dir = other.base_dir.or(self.base_dir);
+        self.editor = other.editor.or(self.editor);
+        self.default_path = other.default_path.or(self.default_path);
+        self.format = other.format.or(self.format);
+        self.pager = other.pager.or(self.pager);
+        self.color = other.color.or(self.color);
+        self.rewrite_links = other.rewrite_links.or(self.rewrite_links);
+        self.templates.extend(other.templates);
+        self.aliases.extend(other.aliases);
+
+        self
+    }
+
+    /**
+     * Replace the command name of the arguments (the first one which is not
+     * a global option) by its alias. Return `None` if it is not an alias.
+     */
+    pub fn expand_alias(&self, args: &[String]) -> Option<Vec<String>> {
+        let mut index = 1;
+        while index < args.len() && args[index].starts_with('-') {
+            index += if VALUE_OPTIONS.contains(&args[index].as_str()) { 2 } else { 1 };
+        }
+        let alias = self.aliases.get(args.get(index)?)?;
+        let mut expanded = args[..index].to_vec();
+        expanded.extend(alias.split_whitespace().map(|arg| arg.to_string()));
+        expanded.extend_from_slice(&args[index + 1..]);
+
+        Some(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn layers() {
+        let origin = Path::new("config.toml");
+        let user = Config::parse(r#"
+            base_dir = "/home/user/zettel"
+            editor = "vim"
+            color = "never"
+
+            [aliases]
+            log = "path log"
+            s = "search -n 5"
+        "#, origin).unwrap();
+        let organization = Config::parse(r#"
+            editor = "nano"
+            default_path = "trunk"
+            rewrite_links = true
+
+            [templates]
+            literature = "templates/literature.md"
+
+            [aliases]
+            log = "path log -t rust"
+        "#, origin).unwrap();
+        let config = user.merge(organization);
+        assert_eq!(Some("nano".to_string()), config.editor);
+        assert_eq!(Some("trunk".to_string()), config.default_path);
+        assert_eq!(Some("never".to_string()), config.color);
+        assert_eq!(Some(true), config.rewrite_links);
+        assert_eq!(Some("/home/user/zettel".to_string()), config.base_dir);
+        assert_eq!(Some(&PathBuf::from("templates/literature.md")), config.templates.get("literature"));
+        assert_eq!(2, config.aliases.len());
+
+        let args = |line: &str| line.split(' ').map(|arg| arg.to_string()).collect::<Vec<String>>();
+        assert_eq!(Some(args("ztln --format json path log -t rust main")), config.expand_alias(&args("ztln --format json log main")));
+        assert_eq!(Some(args("ztln search -n 5 ownership")), config.expand_alias(&args("ztln s ownership")));
+        assert_eq!(None, config.expand_alias(&args("ztln note show HEAD")));
+        assert_eq!(None, config.expand_alias(&args("ztln --format")));
+
+        assert_eq!(ErrorKind::Parse, Config::parse("color = \"blue\"", origin).unwrap_err().kind());
+        assert_eq!(ErrorKind::Parse, Config::parse("unknown = 1", origin).unwrap_err().kind());
+        assert_eq!(ErrorKind::Parse, Config::parse("editor = ", origin).unwrap_err().kind());
+        assert_eq!(Config::default(), Config::read(Path::new("tmp/missing_config.toml")).unwrap());
+
+        std::fs::create_dir_all("tmp/config1").unwrap();
+        std::fs::write("tmp/config1/config.toml", "[templates]\ndaily = \"daily.md\"\nweekly = \"/templates/weekly.md\"\n").unwrap();
+        let config = Config::read(Path::new("tmp/config1/config.toml")).unwrap();
+        let dir = std::env::current_dir().unwrap().join("tmp/config1");
+        assert_eq!(Some(&dir.join("daily.md")), config.templates.get("daily"));
+        assert_eq!(Some(&PathBuf::from("/templates/weekly.md")), config.templates.get("weekly"));
+        std::fs::remove_dir_all("tmp/config1").unwrap();
+    }
+}
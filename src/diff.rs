@@ -0,0 +1,37 @@
+use serde::Serialize;
+use similar::TextDiff;
+
+use crate::note::NoteMetaData;
+
+/**
+Comparison of two locations: their closest common ancestor (the merge base)
+and the notes reachable from each side only, oldest first.
+ */
+#[derive(Debug, Serialize)]
+pub struct PathDiff {
+    pub base: Option<NoteMetaData>,
+    pub left: Vec<NoteMetaData>,
+    pub right: Vec<NoteMetaData>,
+}
+
+/**
+ * Unified diff of two texts, an empty string when they are equal.
+ */
+pub fn unified_diff(left: &str, right: &str, left_name: &str, right_name: &str) -> String {
+    TextDiff::from_lines(left, right)
+        .unified_diff()
+        .header(left_name, right_name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified() {
+        let diff = unified_diff("first\nsecond\n", "first\nthird\n", "a", "b");
+        assert_eq!("--- a\n+++ b\n@@ -1,2 +1,2 @@\n first\n-second\n+third\n", diff);
+        assert_eq!("", unified_diff("same\n", "same\n", "a", "b"));
+    }
+}
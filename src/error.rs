@@ -1,39 +1,169 @@
 use std::error;
 use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
 
-pub type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+pub type Result<T> = std::result::Result<T, ZtlnError>;
 
-#[derive(Debug, Clone, PartialEq)]
+/**
+The families of errors, the command line maps each of them to its own exit
+code so scripts can tell a missing note from a broken organization.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// the operation is refused, the message tells why
+    Invalid,
+    /// a topic, a path, a note or an organization does not exist
+    NotFound,
+    /// the target already exists or the request is ambiguous
+    Conflict,
+    /// an expression given by the user, a meta data file or a configuration
+    /// file cannot be parsed
+    Parse,
+    /// a file of the organization is not in the expected format
+    Corrupted,
+    /// the file system failed
+    Io,
+}
+
+#[derive(Debug)]
 pub enum ZtlnError {
     Default(String),
+    OrganizationAlreadyExists(String),
+    OrganizationDoesNotExist(String),
     TopicDoesNotExist(String),
     TopicAlreadyExists(String),
+    TemplateDoesNotExist(String),
+    SourceDoesNotExist(String),
     PathAlreadyExists(String, String),
     PathDoesNotExist(String, String),
     ParserError(String, Option<String>),
     LocationError(String),
+    LocationSyntaxError(String, usize, String),
+    AmbiguousUuid(String, Vec<String>),
+    /// the operation is refused because of the current state of the notes
+    Conflict(String),
+    Corrupted(PathBuf, String),
+    ConfigError(PathBuf, String),
+    Io { operation: String, path: Option<PathBuf>, source: io::Error },
+}
+
+impl ZtlnError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ZtlnError::Default(_) => ErrorKind::Invalid,
+            ZtlnError::OrganizationDoesNotExist(_)
+                | ZtlnError::TopicDoesNotExist(_)
+                | ZtlnError::TemplateDoesNotExist(_)
+                | ZtlnError::SourceDoesNotExist(_)
+                | ZtlnError::PathDoesNotExist(_, _)
+                | ZtlnError::LocationError(_) => ErrorKind::NotFound,
+            ZtlnError::OrganizationAlreadyExists(_)
+                | ZtlnError::TopicAlreadyExists(_)
+                | ZtlnError::PathAlreadyExists(_, _)
+                | ZtlnError::AmbiguousUuid(_, _)
+                | ZtlnError::Conflict(_) => ErrorKind::Conflict,
+            ZtlnError::ParserError(_, _)
+                | ZtlnError::LocationSyntaxError(_, _, _)
+                | ZtlnError::ConfigError(_, _) => ErrorKind::Parse,
+            ZtlnError::Corrupted(_, _) => ErrorKind::Corrupted,
+            ZtlnError::Io { .. } => ErrorKind::Io,
+        }
+    }
 }
 
 impl fmt::Display for ZtlnError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            ZtlnError::OrganizationAlreadyExists(base_dir)
+                                => write!(f, "→ Given directory '{}' already exists.", base_dir),
+            ZtlnError::OrganizationDoesNotExist(base_dir)
+                                => write!(f, "→ Given path '{}' is not a directory.", base_dir),
             ZtlnError::TopicDoesNotExist(topic)
                                 => write!(f, "→ Topic '{}' does not exist", topic),
             ZtlnError::TopicAlreadyExists(topic)
                                 => write!(f, "→ Topic '{}' does already exist", topic),
+            ZtlnError::TemplateDoesNotExist(name)
+                                => write!(f, "→ Template '{}' does not exist", name),
+            ZtlnError::SourceDoesNotExist(citekey)
+                                => write!(f, "→ Source '{}' does not exist", citekey),
             ZtlnError::PathAlreadyExists(topic, path)
                                 => write!(f, "→ Path '{}/{}' does already exist", topic, path),
             ZtlnError::PathDoesNotExist(topic, path)
                                 => write!(f, "→ Path {}/{} does not exist", topic, path),
-            ZtlnError::ParserError(field, some_msg) 
+            ZtlnError::ParserError(field, some_msg)
                                 => write!(f, "→ Parser error while reading '{}' field. {}", field, some_msg.as_deref().unwrap_or("")),
             ZtlnError::LocationError(location)
                                 => write!(f, "→ Location '{}' does not exist.", location),
-            ZtlnError::Default(message) 
+            ZtlnError::LocationSyntaxError(expr, position, message)
+                                => write!(f, "→ Invalid location expression, {}.\n    {}\n    {}^", message, expr, " ".repeat(*position)),
+            ZtlnError::AmbiguousUuid(short_uuid, candidates)
+                                => write!(f, "→ Short UUID '{}' is ambiguous, candidates are: {}", short_uuid, candidates.join(", ")),
+            ZtlnError::Corrupted(path, message)
+                                => write!(f, "→ File '{}' is corrupted: {}", path.display(), message),
+            ZtlnError::ConfigError(path, message)
+                                => write!(f, "→ Invalid configuration file '{}': {}", path.display(), message),
+            ZtlnError::Io { operation, path: Some(path), source }
+                                => write!(f, "→ Cannot {} '{}': {}", operation, path.display(), source),
+            ZtlnError::Io { operation, path: None, source }
+                                => write!(f, "→ Cannot {}: {}", operation, source),
+            ZtlnError::Default(message) | ZtlnError::Conflict(message)
                                 => write!(f, "→ {}", message),
-                                
+
+        }
+    }
+}
+
+impl error::Error for ZtlnError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ZtlnError::Io { source, .. } => Some(source),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for ZtlnError {}
+impl From<io::Error> for ZtlnError {
+    fn from(source: io::Error) -> Self {
+        ZtlnError::Io { operation: "perform I/O".to_string(), path: None, source }
+    }
+}
+
+/**
+Give the I/O errors the operation and the file they come from.
+ */
+pub trait IoContext<T> {
+    fn context(self, operation: &str, path: impl AsRef<Path>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn context(self, operation: &str, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|source| ZtlnError::Io {
+            operation: operation.to_string(),
+            path: Some(path.as_ref().to_path_buf()),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_kinds() {
+        let result: io::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let error = result.context("read", "base/notes/1234").unwrap_err();
+        assert_eq!(ErrorKind::Io, error.kind());
+        assert_eq!("→ Cannot read 'base/notes/1234': no such file", error.to_string());
+        assert!(error::Error::source(&error).is_some());
+        assert_eq!(ErrorKind::NotFound, ZtlnError::LocationError("main~3".to_string()).kind());
+        assert_eq!(ErrorKind::Conflict, ZtlnError::TopicAlreadyExists("topic1".to_string()).kind());
+        assert_eq!(ErrorKind::Conflict, ZtlnError::Conflict("Note is already promoted".to_string()).kind());
+        assert_eq!(ErrorKind::Parse, ZtlnError::LocationSyntaxError("main~".to_string(), 5, "number expected".to_string()).kind());
+        assert_eq!(ErrorKind::Corrupted, ZtlnError::Corrupted(PathBuf::from("index"), "invalid".to_string()).kind());
+        assert_eq!(ErrorKind::Invalid, ZtlnError::Default("Note is empty".to_string()).kind());
+    }
+}
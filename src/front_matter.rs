@@ -0,0 +1,124 @@
+use serde::Deserialize;
+use crate::error::{Result, ZtlnError};
+
+/**
+Optional block at the top of a note, between `---` lines (YAML) or `+++`
+lines (TOML):
+
+```text
+---
+title: Ownership
+tags: [rust, memory]
+refs: [main~2, "#borrowing"]
+---
+```
+ */
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    /// keywords the note is tagged with
+    pub tags: Vec<String>,
+    /// locations of the notes the note refers to
+    pub refs: Vec<String>,
+}
+
+impl FrontMatter {
+    /**
+     * Split the content of a note into its front-matter, if any, and its
+     * body. Keys other than the ones of `FrontMatter` are ignored and, without
+     * a closing line, the first `---` is a thematic break of the body.
+     */
+    pub fn split(content: &str) -> Result<(Option<FrontMatter>, &str)> {
+        let delimiter = match content.lines().next().map(|line| line.trim_end()) {
+            Some(delimiter) if delimiter == "---" || delimiter == "+++" => delimiter,
+            _ => return Ok((None, content)),
+        };
+        let start = content.find('\n').map_or(content.len(), |i| i + 1);
+        let mut end = start;
+        for line in content[start..].split_inclusive('\n') {
+            if line.trim_end() == delimiter {
+                let block = &content[start..end];
+                let front_matter = if delimiter == "---" {
+                    serde_yaml::from_str::<Option<FrontMatter>>(block)
+                        .map(|front_matter| front_matter.unwrap_or_default())
+                        .map_err(|e| e.to_string())
+                } else {
+                    toml::from_str(block).map_err(|e| e.message().to_string())
+                }.map_err(|message| ZtlnError::ParserError("front-matter".to_string(), Some(message)))?;
+
+                return Ok((Some(front_matter), &content[end + line.len()..]));
+            }
+            end += line.len();
+        }
+
+        Ok((None, content))
+    }
+
+    /**
+     * The title of a note: the one of its front-matter, otherwise the first
+     * line of its body without the heading marks and thematic breaks.
+     */
+    pub fn title(content: &str) -> String {
+        match FrontMatter::split(content) {
+            Ok((Some(FrontMatter { title: Some(title), .. }), _)) => title,
+            Ok((_, body)) => first_line(body),
+            Err(_) => first_line(content),
+        }
+    }
+}
+
+fn first_line(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty() && *line != "---")
+        .unwrap_or("")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn split() {
+        let content = "---\ntitle: Ownership\ntags: [rust, memory]\nrefs:\n  - main~2\n  - \"#borrowing\"\n---\n# Each value has an owner\n";
+        let (front_matter, body) = FrontMatter::split(content).unwrap();
+        assert_eq!(Some(FrontMatter {
+            title: Some("Ownership".to_string()),
+            tags: vec!["rust".to_string(), "memory".to_string()],
+            refs: vec!["main~2".to_string(), "#borrowing".to_string()],
+        }), front_matter);
+        assert_eq!("# Each value has an owner\n", body);
+        assert_eq!("Ownership", FrontMatter::title(content));
+
+        let (front_matter, body) = FrontMatter::split("+++\ntags = [\"rust\"]\n+++\nbody").unwrap();
+        assert_eq!(vec!["rust".to_string()], front_matter.unwrap().tags);
+        assert_eq!("body", body);
+        assert_eq!(Some(FrontMatter::default()), FrontMatter::split("---\n---\n").unwrap().0);
+        assert_eq!((None, "text\n---\n"), FrontMatter::split("text\n---\n").unwrap());
+        assert_eq!("Each value has an owner", FrontMatter::title("\n# Each value has an owner\n"));
+
+        assert_eq!(ErrorKind::Parse, FrontMatter::split("---\ntags: rust\n---\n").unwrap_err().kind());
+        assert_eq!(ErrorKind::Parse, FrontMatter::split("+++\ntags = \"rust\"\n+++\n").unwrap_err().kind());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let content = "---\ntitle: Ownership\nauthor: me\ndate: 2021-03-01\naliases: [owner]\n---\nbody";
+        let (front_matter, body) = FrontMatter::split(content).unwrap();
+        assert_eq!(Some("Ownership".to_string()), front_matter.unwrap().title);
+        assert_eq!("body", body);
+        let (front_matter, _) = FrontMatter::split("+++\nauthor = \"me\"\ntags = [\"rust\"]\n+++\n").unwrap();
+        assert_eq!(vec!["rust".to_string()], front_matter.unwrap().tags);
+    }
+
+    #[test]
+    fn thematic_break() {
+        let content = "---\n\nEach value has an owner.\n";
+        assert_eq!((None, content), FrontMatter::split(content).unwrap());
+        assert_eq!("Each value has an owner.", FrontMatter::title(content));
+        assert_eq!((None, "---\ntitle: a\n"), FrontMatter::split("---\ntitle: a\n").unwrap());
+    }
+}
@@ -0,0 +1,42 @@
+use serde_json::{json, Value};
+
+use crate::error::Result;
+use crate::note::NoteMetaData;
+use crate::organization::Organization;
+
+/**
+ * JSON representation of a topic.
+ */
+pub fn topic_to_json(orga: &mut Organization, topic: &str) -> Result<Value> {
+    Ok(json!({
+        "name": topic,
+        "current": orga.get_current_topic()?.as_deref() == Some(topic),
+        "archived": orga.is_topic_archived(topic),
+        "description": orga.get_topic_description(topic)?,
+        "current_path": orga.get_current_path(topic)?,
+    }))
+}
+
+/**
+ * JSON representation of a path with the UUID of its head note.
+ */
+pub fn path_to_json(orga: &mut Organization, topic: &str, path: &str) -> Result<Value> {
+    let head = orga.solve_location(&format!("{}/{}", topic, path))?.map(|metadata| metadata.note_id);
+    Ok(json!({
+        "topic": topic,
+        "name": path,
+        "current": orga.get_current_path(topic)?.as_deref() == Some(path),
+        "head": head,
+    }))
+}
+
+/**
+ * JSON representation of a note with its location and content.
+ */
+pub fn note_to_json(orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+    Ok(json!({
+        "metadata": metadata,
+        "location": orga.locate_note(metadata)?,
+        "content": orga.get_note_content(metadata.note_id)?,
+    }))
+}
@@ -1,13 +1,30 @@
-#[macro_use(lazy_static)] extern crate lazy_static;
 mod error;
 mod organization;
 mod store;
 mod note;
+mod search;
+mod location;
+mod diff;
+mod json;
+mod server;
+mod tui;
+mod config;
+mod front_matter;
+mod links;
+mod bibtex;
 
-pub use error::{Result, ZtlnError};
+pub use error::{Result, ZtlnError, ErrorKind, IoContext};
 pub use organization::Organization;
 pub use store::{Store, IOStore};
-pub use note::NoteMetaData;
+pub use note::{NoteMetaData, NoteKind, AttachmentProblem};
+pub use search::{SearchResult, GrepResult};
+pub use diff::PathDiff;
+pub use json::{topic_to_json, path_to_json, note_to_json};
+pub use server::serve;
+pub use tui::tui;
+pub use config::Config;
+pub use front_matter::FrontMatter;
+pub use bibtex::Source;
 
 #[cfg(test)]
 mod tests {
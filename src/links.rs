@@ -0,0 +1,138 @@
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/**
+A `[[LOCATION]]` or `[[LOCATION|label]]` link in the content of a note.
+ */
+#[derive(Debug, PartialEq)]
+pub struct WikiLink {
+    /// position of the location in the content
+    pub range: Range<usize>,
+    pub location: String,
+}
+
+/**
+The wiki-links of a content, in order. The ones in code blocks or code spans
+are not links (`grid[[i]]`).
+ */
+pub fn wiki_links(content: &str) -> Vec<WikiLink> {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    let regex = REGEX.get_or_init(|| Regex::new(r"\[\[([^\[\]|\n]+)(\|[^\[\]\n]*)?\]\]").unwrap());
+    let code = code_ranges(content);
+    regex.captures_iter(content)
+        .filter(|captures| !code.iter().any(|range| range.contains(&captures.get(0).unwrap().start())))
+        .filter_map(|captures| captures.get(1))
+        .filter(|location| !location.as_str().trim().is_empty())
+        .map(|location| {
+            let text = location.as_str();
+            let start = location.start() + text.len() - text.trim_start().len();
+            let location = text.trim().to_string();
+            WikiLink { range: start..start + location.len(), location }
+        })
+        .collect()
+}
+
+/**
+Positions of the fenced code blocks (```` ``` ```` or `~~~`) and of the code
+spans of a Markdown content.
+ */
+fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence: Option<(&str, usize)> = None;
+    let mut position = 0;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker_length = trimmed.find(|c| c != '`' && c != '~').unwrap_or(trimmed.len());
+        let marker = &trimmed[..marker_length];
+        let is_fence = marker.len() >= 3 && marker.chars().all(|c| marker.starts_with(c));
+        match fence {
+            Some((opening, start)) => {
+                if is_fence && marker.starts_with(opening) && trimmed[marker.len()..].trim().is_empty() {
+                    ranges.push(start..position + line.len());
+                    fence = None;
+                }
+            },
+            None if is_fence => fence = Some((marker, position)),
+            None => ranges.extend(code_spans(line).into_iter()
+                .map(|span| position + span.start..position + span.end)),
+        }
+        position += line.len();
+    }
+    if let Some((_, start)) = fence {
+        ranges.push(start..content.len());
+    }
+
+    ranges
+}
+
+/**
+A code span opens with a run of backticks and closes with the next run of the
+same length.
+ */
+fn code_spans(line: &str) -> Vec<Range<usize>> {
+    let runs: Vec<Range<usize>> = line.match_indices('`')
+        .fold(Vec::new(), |mut runs: Vec<Range<usize>>, (index, _)| {
+            match runs.last_mut() {
+                Some(run) if run.end == index => run.end += 1,
+                _ => runs.push(index..index + 1),
+            }
+            runs
+        });
+    let mut spans = Vec::new();
+    let mut index = 0;
+    while index < runs.len() {
+        let opening = &runs[index];
+        match runs[index + 1..].iter().position(|run| run.len() == opening.len()) {
+            Some(offset) => {
+                spans.push(opening.start..runs[index + 1 + offset].end);
+                index += offset + 2;
+            },
+            None => index += 1,
+        }
+    }
+
+    spans
+}
+
+/**
+Replace the location of each link by the UUID it was solved into, labels are
+kept.
+ */
+pub fn rewrite_links(content: &str, links: &[WikiLink], uuids: &[Uuid]) -> String {
+    let mut rewritten = String::with_capacity(content.len());
+    let mut position = 0;
+    for (link, uuid) in links.iter().zip(uuids) {
+        rewritten.push_str(&content[position..link.range.start]);
+        rewritten.push_str(&uuid.to_string());
+        position = link.range.end;
+    }
+    rewritten.push_str(&content[position..]);
+
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_rewrite() {
+        let content = "See [[a1b2c3d4]] and [[ topic1/main:-2 | the origin]], not [[]] nor [single].";
+        let links = wiki_links(content);
+        assert_eq!(vec!["a1b2c3d4", "topic1/main:-2"], links.iter().map(|link| link.location.as_str()).collect::<Vec<&str>>());
+
+        let uuids = vec![Uuid::new_v4(), Uuid::new_v4()];
+        assert_eq!(
+            format!("See [[{}]] and [[ {} | the origin]], not [[]] nor [single].", uuids[0], uuids[1]),
+            rewrite_links(content, &links, &uuids)
+        );
+        assert!(wiki_links("[[broken\nlink]]").is_empty());
+
+        let content = "Use `grid[[i]]` or ``x[[0]]``, see [[main]].\n```rust\nlet y = x[[1]];\n```\n~~~\n[[in fence]]\n~~~\n[[HEAD]]";
+        assert_eq!(vec!["main", "HEAD"], wiki_links(content).iter().map(|link| link.location.as_str()).collect::<Vec<&str>>());
+        assert!(wiki_links("```\nnot closed [[main]]\n").is_empty());
+        assert_eq!(1, wiki_links("a lone ` then [[main]]").len());
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::error::ZtlnError;
+use crate::store::SHORT_UUID_LENGTH;
+
+type Result<T> = std::result::Result<T, ZtlnError>;
+
+/**
+The starting point of a location expression.
+ */
+#[derive(Debug, PartialEq)]
+pub enum Anchor {
+    /// `[topic/]path`, the path may be `HEAD`
+    Path { topic: Option<String>, path: String },
+    /// a UUID or a prefix of at least 8 characters, shorter prefixes are
+    /// parsed as a path and only tried as a UUID when no topic is given
+    Uuid(String),
+    /// `#keyword[:latest|:first]`
+    Tag { keyword: String, latest: bool },
+    /// `title:words`, the note which title matches the words best
+    Title(String),
+}
+
+/**
+A move in the history of notes applied after the anchor is solved.
+ */
+#[derive(Debug, PartialEq)]
+pub enum Modifier {
+    /// `:-N` or `~N`, go back N ancestors
+    Ancestor(usize),
+    /// `^` or `^N`, go to the Nth parent
+    Parent(usize),
+    /// `@YYYY-MM-DD`, go back to the last note created on or before that day
+    Date(NaiveDate),
+    /// `:/regex/`, go back to the first note which content matches
+    Search(String),
+}
+
+/**
+Parsed location expression:
+
+```text
+location := 'title:' words | anchor modifier*
+anchor   := '#' name [':latest' | ':first'] | uuid | [name '/'] name
+modifier := ':-' N | '~' [N] | '^' [N] | '@' date | ':/' regex '/'
+```
+ */
+#[derive(Debug, PartialEq)]
+pub struct Location {
+    pub anchor: Anchor,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Location {
+    pub fn parse(expr: &str) -> Result<Self> {
+        Parser { expr, chars: expr.char_indices().collect(), position: 0 }.parse()
+    }
+}
+
+struct Parser<'a> {
+    expr: &'a str,
+    chars: Vec<(usize, char)>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(&mut self) -> Result<Location> {
+        if self.expr.is_empty() {
+            return Err(self.error("empty location"));
+        }
+        // the words of a title may contain any character, they end the location
+        if let Some(words) = self.rest().strip_prefix("title:").filter(|words| !words.starts_with(['-', '/'])) {
+            if words.trim().is_empty() {
+                return Err(self.error_at(6, "expected the words of a title"));
+            }
+            return Ok(Location { anchor: Anchor::Title(words.trim().to_string()), modifiers: Vec::new() });
+        }
+        let anchor = self.parse_anchor()?;
+        let mut modifiers = Vec::new();
+        while self.peek().is_some() {
+            modifiers.push(self.parse_modifier()?);
+        }
+
+        Ok(Location { anchor, modifiers })
+    }
+
+    fn parse_anchor(&mut self) -> Result<Anchor> {
+        if self.peek() == Some('#') {
+            self.position += 1;
+            let keyword = self.parse_name("tag")?;
+            let latest = if self.rest().starts_with(":latest") {
+                self.position += 7;
+                true
+            } else if self.rest().starts_with(":first") {
+                self.position += 6;
+                false
+            } else { true };
+
+            return Ok(Anchor::Tag { keyword, latest });
+        }
+        let name = self.parse_name("topic or path")?;
+        if self.peek() == Some('/') {
+            self.position += 1;
+            let path = self.parse_name("path")?;
+            return Ok(Anchor::Path { topic: Some(name), path });
+        }
+        if is_uuid_prefix(&name, SHORT_UUID_LENGTH) {
+            Ok(Anchor::Uuid(name))
+        } else {
+            Ok(Anchor::Path { topic: None, path: name })
+        }
+    }
+
+    fn parse_modifier(&mut self) -> Result<Modifier> {
+        let start = self.position;
+        match self.next() {
+            Some(':') => match self.next() {
+                Some('-') => Ok(Modifier::Ancestor(self.parse_number()?
+                    .ok_or_else(|| self.error("expected a number after ':-'"))?)),
+                Some('/') => self.parse_search(),
+                _ => Err(self.error_at(start, "expected ':-N' or ':/regex/' after ':'")),
+            },
+            Some('~') => Ok(Modifier::Ancestor(self.parse_number()?.unwrap_or(1))),
+            Some('^') => Ok(Modifier::Parent(self.parse_number()?.unwrap_or(1))),
+            Some('@') => self.parse_date(),
+            Some('/') => Err(self.error_at(start, "a location has at most one '/' separating the topic from the path")),
+            _ => Err(self.error_at(start, "expected a modifier (':-N', '~N', '^', '@date' or ':/regex/')")),
+        }
+    }
+
+    fn parse_name(&mut self, what: &str) -> Result<String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.error(&format!("expected a {} name", what)));
+        }
+
+        Ok(self.slice(start, self.position).to_string())
+    }
+
+    fn parse_number(&mut self) -> Result<Option<usize>> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Ok(None);
+        }
+        let number = self.slice(start, self.position).parse::<usize>()
+            .map_err(|_| self.error_at(start, "number is too big"))?;
+
+        Ok(Some(number))
+    }
+
+    fn parse_date(&mut self) -> Result<Modifier> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '-') {
+            self.position += 1;
+        }
+        let date = NaiveDate::parse_from_str(self.slice(start, self.position), "%Y-%m-%d")
+            .map_err(|_| self.error_at(start, "expected a date formatted as YYYY-MM-DD"))?;
+
+        Ok(Modifier::Date(date))
+    }
+
+    fn parse_search(&mut self) -> Result<Modifier> {
+        let start = self.position;
+        let mut pattern = String::new();
+        loop {
+            match self.next() {
+                Some('\\') if self.peek() == Some('/') => {
+                    self.position += 1;
+                    pattern.push('/');
+                },
+                Some('/') => break,
+                Some(c) => pattern.push(c),
+                None => return Err(self.error_at(start, "unterminated regular expression, expected a closing '/'")),
+            }
+        }
+        if pattern.is_empty() {
+            return Err(self.error_at(start, "empty regular expression"));
+        }
+        Regex::new(&pattern)
+            .map_err(|e| self.error_at(start, &format!("invalid regular expression ({})", e)))?;
+
+        Ok(Modifier::Search(pattern))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).map(|(_, c)| *c)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn rest(&self) -> &str {
+        self.slice(self.position, self.chars.len())
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &str {
+        let offset = |position: usize| self.chars.get(position).map_or(self.expr.len(), |(offset, _)| *offset);
+
+        &self.expr[offset(start)..offset(end)]
+    }
+
+    fn error(&self, message: &str) -> ZtlnError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at(&self, position: usize, message: &str) -> ZtlnError {
+        ZtlnError::LocationSyntaxError(self.expr.to_string(), position, message.to_string())
+    }
+}
+
+/**
+ * Test if the given text is the beginning of a UUID with at least the given
+ * number of characters.
+ */
+pub fn is_uuid_prefix(text: &str, min_length: usize) -> bool {
+    const TEMPLATE: &str = "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx";
+
+    text.len() >= min_length
+        && text.len() <= TEMPLATE.len()
+        && text.chars().zip(TEMPLATE.chars()).all(|(c, t)| if t == '-' { c == '-' } else { c.is_ascii_hexdigit() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(topic: Option<&str>, path: &str) -> Anchor {
+        Anchor::Path { topic: topic.map(|t| t.to_string()), path: path.to_string() }
+    }
+
+    #[test]
+    fn parse_ok() {
+        let expressions = vec![
+            ("main", Location { anchor: path(None, "main"), modifiers: vec![] }),
+            ("my-path", Location { anchor: path(None, "my-path"), modifiers: vec![] }),
+            ("topic1/HEAD:-3", Location { anchor: path(Some("topic1"), "HEAD"), modifiers: vec![Modifier::Ancestor(3)] }),
+            ("main~2^", Location { anchor: path(None, "main"), modifiers: vec![Modifier::Ancestor(2), Modifier::Parent(1)] }),
+            ("main~", Location { anchor: path(None, "main"), modifiers: vec![Modifier::Ancestor(1)] }),
+            ("a1b2c3d4:-2", Location { anchor: Anchor::Uuid("a1b2c3d4".to_string()), modifiers: vec![Modifier::Ancestor(2)] }),
+            (
+                "44a0f45f-22b6-4675-a277-e196d8881ca8",
+                Location { anchor: Anchor::Uuid("44a0f45f-22b6-4675-a277-e196d8881ca8".to_string()), modifiers: vec![] }
+            ),
+            ("44a0f45f-22b6", Location { anchor: Anchor::Uuid("44a0f45f-22b6".to_string()), modifiers: vec![] }),
+            ("44a0f45f-zz", Location { anchor: path(None, "44a0f45f-zz"), modifiers: vec![] }),
+            ("#rust", Location { anchor: Anchor::Tag { keyword: "rust".to_string(), latest: true }, modifiers: vec![] }),
+            ("#rust:first~1", Location { anchor: Anchor::Tag { keyword: "rust".to_string(), latest: false }, modifiers: vec![Modifier::Ancestor(1)] }),
+            ("#rust:latest", Location { anchor: Anchor::Tag { keyword: "rust".to_string(), latest: true }, modifiers: vec![] }),
+            (
+                "main@2024-05-01",
+                Location { anchor: path(None, "main"), modifiers: vec![Modifier::Date(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap())] }
+            ),
+            ("title:ownership rules~1", Location { anchor: Anchor::Title("ownership rules~1".to_string()), modifiers: vec![] }),
+            ("title:-1", Location { anchor: path(None, "title"), modifiers: vec![Modifier::Ancestor(1)] }),
+            (
+                "main:/own(er)?ship\\/s/",
+                Location { anchor: path(None, "main"), modifiers: vec![Modifier::Search("own(er)?ship/s".to_string())] }
+            ),
+        ];
+        for (expr, location) in expressions {
+            assert_eq!(location, Location::parse(expr).unwrap(), "parsing '{}'", expr);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let expressions = vec![
+            ("", 0),
+            ("tata:toto", 4),
+            ("tata:+1", 4),
+            ("tata/toto/tete", 9),
+            ("main@2024-13-01", 5),
+            ("main:/unterminated", 6),
+            ("main:/(/", 6),
+            ("#", 1),
+            ("main#", 4),
+            ("main:-", 6),
+            ("title: ", 6),
+        ];
+        for (expr, position) in expressions {
+            match Location::parse(expr) {
+                Err(ZtlnError::LocationSyntaxError(_, p, _)) => assert_eq!(position, p, "error position in '{}'", expr),
+                other => panic!("'{}' should not parse: {:?}", expr, other),
+            }
+        }
+    }
+}
@@ -4,18 +4,182 @@ use std::process::Command;
 use rand::Rng; 
 use rand::distributions::Alphanumeric;
 use std::env;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Read, Write};
+use std::process::Stdio;
+use std::str::FromStr;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/**
+Output format of the commands: human readable text or JSON for scripts.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/**
+Options shared by all the commands: the output format and the preferences of
+the configuration files.
+ */
+struct Context {
+    format: Format,
+    color: bool,
+    config: Config,
+}
+
+impl Context {
+    fn new(config: Config, format: Option<Format>) -> Self {
+        // the configuration is checked when loaded
+        let format = format
+            .or_else(|| config.format.as_deref().and_then(|f| Format::from_str(f).ok()))
+            .unwrap_or(Format::Text);
+        let color = match config.color.as_deref() {
+            Some("always") => true,
+            Some("never") => false,
+            _ => std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        };
+
+        Self { format, color, config }
+    }
+
+    /**
+     * Open the organization with the preferences of the configuration.
+     */
+    fn open<'a>(&self, base_dir: &'a str) -> Result<Organization<'a>> {
+        let mut orga = Organization::new(Store::attach(base_dir)?);
+        orga.configure(&self.config)?;
+
+        Ok(orga)
+    }
+
+    /**
+     * Print a warning on the standard error.
+     */
+    fn warn(&self, warning: &str) {
+        match self.format {
+            Format::Text => eprintln!("WARNING: {}", warning),
+            Format::Json => eprintln!("{}", json!({ "warning": warning })),
+        }
+    }
+
+    /**
+     * Write the given text in a temporary file, open it with the configured
+     * editor, `$EDITOR` otherwise (default: vi), and return the file name
+     * once the editor exits.
+     */
+    fn edit(&self, content: &str) -> Result<String> {
+        let f = &temp_filename();
+        if !content.is_empty() {
+            std::fs::write(f, content)?;
+        }
+        let editor = self.config.editor.clone()
+            .or_else(|| env::var("EDITOR").ok())
+            .unwrap_or_else(|| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        Command::new(words.next().unwrap_or("vi"))
+            .args(words)
+            .arg(f)
+            .status()?;
+
+        Ok(f.to_string())
+    }
+
+    /**
+     * Print a text through the configured pager when the output is a
+     * terminal.
+     */
+    fn page(&self, text: &str) -> Result<()> {
+        let pager = match self.config.pager.as_ref() {
+            Some(pager) if std::io::stdout().is_terminal() => pager,
+            _ => {
+                print!("{}", text);
+                return Ok(());
+            },
+        };
+        let mut words = pager.split_whitespace();
+        let mut child = Command::new(words.next().unwrap_or("less"))
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| ZtlnError::Default(format!("Cannot launch the pager '{}': {}", pager, e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // the pager may be left before reading everything
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        child.wait()?;
+
+        Ok(())
+    }
+
+    /**
+     * Highlight a short UUID, like Git does for commits, when colors are on.
+     */
+    fn paint(&self, short_uuid: &str) -> String {
+        if self.color {
+            format!("\x1b[33m{}\x1b[0m", short_uuid)
+        } else {
+            short_uuid.to_string()
+        }
+    }
+}
+
+/**
+A command working on an organization: `run` does the work, then its output is
+rendered once, as JSON with `--format json` or as text.
+ */
+trait Render {
+    type Output;
+
+    fn run(&self, orga: &mut Organization, ctx: &Context) -> Result<Self::Output>;
+    fn json(&self, orga: &mut Organization, output: &Self::Output) -> Result<Value>;
+    fn text(&self, orga: &mut Organization, ctx: &Context, output: &Self::Output) -> Result<()>;
+
+    fn render(&self, orga: &mut Organization, ctx: &Context, output: &Self::Output) -> Result<()> {
+        match ctx.format {
+            Format::Json => print_json(self.json(orga, output)?),
+            Format::Text => self.text(orga, ctx, output),
+        }
+    }
+
+    fn execute(&self, orga: &mut Organization, ctx: &Context) -> Result<()> {
+        let output = self.run(orga, ctx)?;
+        self.render(orga, ctx, &output)
+    }
+}
 
 #[derive(Debug, StructOpt)]
 struct MainOpt {
-    #[structopt(long, env="ZTLN_BASE_DIR", help="organization directory path")]
-    base_dir: String,
+    #[structopt(long, env="ZTLN_BASE_DIR", help="organization directory path (default: base_dir of the configuration)")]
+    base_dir: Option<String>,
+    #[structopt(long, global=true, possible_values=&["text", "json"], help="output format (default: text)")]
+    format: Option<Format>,
     #[structopt(subcommand)]
     command: MainCommand,
 }
 
 impl MainOpt {
-    fn execute(&self) -> Result<()> {
-        self.command.execute(&(self.base_dir))
+    fn execute(&self, ctx: &Context) -> Result<()> {
+        let base_dir = self.base_dir.as_ref()
+            .or(ctx.config.base_dir.as_ref())
+            .ok_or_else(|| ZtlnError::Default(
+                "No organization given, use --base-dir, ZTLN_BASE_DIR or base_dir in the configuration file.".to_string()
+            ))?;
+        self.command.execute(base_dir, ctx)
     }
 }
 
@@ -33,17 +197,32 @@ enum MainCommand {
     Note(NoteCommand),
     #[structopt(about="Manage tags.")]
     Tag(TagCommand),
+    #[structopt(about="Manage bibliographic sources.")]
+    Source(SourceCommand),
+    #[structopt(about="Search notes contents.")]
+    Search(SearchCommand),
+    #[structopt(about="Search notes contents with a regular expression.")]
+    Grep(GrepCommand),
+    #[structopt(about="Serve a local HTTP JSON API.")]
+    Serve(ServeCommand),
+    #[structopt(about="Browse the organization in a terminal interface.")]
+    Tui(TuiCommand),
 }
 
 impl MainCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
         match self {
-            MainCommand::Info(cmd) => cmd.execute(base_dir),
-            MainCommand::Init(cmd) => cmd.execute(base_dir),
-            MainCommand::Topic(cmd) => cmd.execute(base_dir),
-            MainCommand::Path(cmd) => cmd.execute(base_dir),
-            MainCommand::Note(cmd) => cmd.execute(base_dir),
-            MainCommand::Tag(cmd) => cmd.execute(base_dir),
+            MainCommand::Info(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Init(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Topic(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Path(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Note(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Tag(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Source(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Search(cmd) => cmd.execute(&mut ctx.open(base_dir)?, ctx),
+            MainCommand::Grep(cmd) => cmd.execute(&mut ctx.open(base_dir)?, ctx),
+            MainCommand::Serve(cmd) => cmd.execute(base_dir, ctx),
+            MainCommand::Tui(cmd) => cmd.execute(base_dir, ctx),
         }
     }
 }
@@ -52,18 +231,23 @@ impl MainCommand {
 struct InfoCommand {}
 
 impl InfoCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
-        let mut orga = Organization::new(Store::attach(base_dir)?);
-        println!("Organization located at: {}", base_dir);
-        let current_topic = orga.get_current_topic();
-        if let Some(topic) = current_topic {
-            let topic = topic;
-            println!("Current topic: {}", &topic);
-            println!("Current path: {}", orga.get_current_path(&topic)?.unwrap_or_else(|| "None".to_string()));
-        } else {
-            println!("Current topic: None");
-            println!("Current path: None");
-            println!("Use `ztln topic create` to create a new topic.");
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
+        let current_topic = orga.get_current_topic()?;
+        let current_path = match current_topic.as_ref() {
+            Some(topic) => orga.get_current_path(topic)?,
+            None => None,
+        };
+        match ctx.format {
+            Format::Json => print_json(json!({ "base_dir": base_dir, "topic": current_topic, "path": current_path }))?,
+            Format::Text => {
+                println!("Organization located at: {}", base_dir);
+                println!("Current topic: {}", current_topic.as_deref().unwrap_or("None"));
+                println!("Current path: {}", current_path.as_deref().unwrap_or("None"));
+                if current_topic.is_none() {
+                    println!("Use `ztln topic create` to create a new topic.");
+                }
+            },
         }
         Ok(())
     }
@@ -73,10 +257,15 @@ impl InfoCommand {
 struct InitCommand {}
 
 impl InitCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
         Store::init(base_dir)?;
-        println!("Ztln version {} organization intialized at '{}'.", env!("CARGO_PKG_VERSION"), base_dir);
-        Ok(())
+        match ctx.format {
+            Format::Json => print_json(json!({ "base_dir": base_dir, "version": env!("CARGO_PKG_VERSION") })),
+            Format::Text => {
+                println!("Ztln version {} organization intialized at '{}'.", env!("CARGO_PKG_VERSION"), base_dir);
+                Ok(())
+            },
+        }
     }
 }
 
@@ -88,42 +277,98 @@ enum TopicCommand {
     List(ListTopicCommand),
     #[structopt(about="set the default topic")]
     Default(DefaultTopicCommand),
+    #[structopt(about="rename a topic")]
+    Rename(RenameTopicCommand),
+    #[structopt(about="delete a topic and all its notes")]
+    Delete(DeleteTopicCommand),
+    #[structopt(about="hide a topic from the topics list")]
+    Archive(ArchiveTopicCommand),
+    #[structopt(about="show an archived topic again in the topics list")]
+    Unarchive(UnarchiveTopicCommand),
+    #[structopt(about="show or set the description of a topic")]
+    Describe(DescribeTopicCommand),
 }
 
 impl TopicCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
-        let mut orga = Organization::new(Store::attach(base_dir)?);
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
         match self {
-            TopicCommand::Create(cmd) => cmd.execute(&mut orga),
-            TopicCommand::List(cmd) => cmd.execute(&mut orga),
-            TopicCommand::Default(cmd) => cmd.execute(&mut orga),
+            TopicCommand::Create(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::List(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Default(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Rename(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Delete(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Archive(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Unarchive(cmd) => cmd.execute(&mut orga, ctx),
+            TopicCommand::Describe(cmd) => cmd.execute(&mut orga, ctx),
         }
     }
 }
 
 #[derive(Debug, StructOpt)]
 struct CreateTopicCommand {
-    topic_name: String
+    topic_name: String,
+    #[structopt(short, long, help="description of the topic")]
+    description: Option<String>,
+    #[structopt(short, long, help="name of the topic's first path (default: main)")]
+    main_path: Option<String>,
 }
 
-impl CreateTopicCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        orga.create_topic(&self.topic_name)
+impl Render for CreateTopicCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        match self.main_path.as_ref() {
+            Some(main_path) => orga.create_topic_with_main_path(&self.topic_name, main_path)?,
+            None => orga.create_topic(&self.topic_name)?,
+        }
+        if let Some(description) = self.description.as_ref() {
+            orga.set_topic_description(&self.topic_name, description)?;
+        }
+        Ok(())
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        topic_to_json(orga, &self.topic_name)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        Ok(())
     }
 }
 
 #[derive(Debug, StructOpt)]
-struct ListTopicCommand {}
+struct ListTopicCommand {
+    #[structopt(short, long, help="also list archived topics")]
+    all: bool,
+}
+
+impl Render for ListTopicCommand {
+    type Output = Vec<String>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<String>> {
+        orga.get_topics_list(self.all)
+    }
+
+    fn json(&self, orga: &mut Organization, list: &Vec<String>) -> Result<Value> {
+        let mut topics = Vec::new();
+        for topic in list {
+            topics.push(topic_to_json(orga, topic)?);
+        }
+        Ok(Value::Array(topics))
+    }
 
-impl ListTopicCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let list = orga.get_topics_list();
+    fn text(&self, orga: &mut Organization, _ctx: &Context, list: &Vec<String>) -> Result<()> {
         if list.is_empty() {
             println!("No topics.");
         } else {
-            let current = orga.get_current_topic().unwrap_or_else(|| "".to_string());
+            let current = orga.get_current_topic()?.unwrap_or_default();
             for topic in list {
-                println!("{} {}", if topic == current { "→" } else { " " }, topic);
+                let archived = if orga.is_topic_archived(topic) { " (archived)" } else { "" };
+                let description = orga.get_topic_description(topic)?
+                    .and_then(|d| d.lines().next().map(|line| format!(" — {}", line)))
+                    .unwrap_or_default();
+                println!("{} {}{}{}", if *topic == current { "→" } else { " " }, topic, archived, description);
             }
         }
         Ok(())
@@ -135,14 +380,161 @@ struct DefaultTopicCommand {
     topic_name: String,
 }
 
-impl DefaultTopicCommand {
-    pub fn execute(&self, orga: &mut Organization) -> Result<()> {
+impl Render for DefaultTopicCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
         orga.set_current_topic(&self.topic_name)
     }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        topic_to_json(orga, &self.topic_name)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct RenameTopicCommand {
+    topic_name: String,
+    new_name: String,
+}
+
+impl Render for RenameTopicCommand {
+    type Output = usize;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<usize> {
+        orga.rename_topic(&self.topic_name, &self.new_name)
+    }
+
+    fn json(&self, orga: &mut Organization, count: &usize) -> Result<Value> {
+        Ok(json!({
+            "topic": topic_to_json(orga, &self.new_name)?,
+            "previous_name": self.topic_name,
+            "notes_updated": count,
+        }))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, count: &usize) -> Result<()> {
+        println!("Topic '{}' renamed to '{}' ({} notes updated).", self.topic_name, self.new_name, count);
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct DeleteTopicCommand {
+    #[structopt(short, long, help="delete even if notes of other topics refer to this topic notes")]
+    force: bool,
+    topic_name: String,
+}
+
+impl Render for DeleteTopicCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.delete_topic(&self.topic_name, self.force)
+    }
+
+    fn json(&self, _orga: &mut Organization, deleted: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!({ "topic": self.topic_name, "deleted_notes": deleted }))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, deleted: &Vec<NoteMetaData>) -> Result<()> {
+        println!("Topic '{}' deleted ({} notes removed).", self.topic_name, deleted.len());
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct ArchiveTopicCommand {
+    topic_name: String,
+}
+
+impl Render for ArchiveTopicCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        orga.archive_topic(&self.topic_name, true)
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        topic_to_json(orga, &self.topic_name)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct UnarchiveTopicCommand {
+    topic_name: String,
+}
+
+impl Render for UnarchiveTopicCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        orga.archive_topic(&self.topic_name, false)
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        topic_to_json(orga, &self.topic_name)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct DescribeTopicCommand {
+    topic_name: String,
+    #[structopt(help="new description, an empty text removes it")]
+    description: Option<String>,
+    #[structopt(short, long, conflicts_with="description", help="edit the description with $EDITOR")]
+    edit: bool,
+}
+
+impl Render for DescribeTopicCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, ctx: &Context) -> Result<()> {
+        if let Some(description) = self.description.as_ref() {
+            orga.set_topic_description(&self.topic_name, description)?;
+        } else if self.edit {
+            let current = orga.get_topic_description(&self.topic_name)?.unwrap_or_default();
+            let filename = ctx.edit(&current)?;
+            // the editor does not create the file when quitting without saving
+            if std::path::Path::new(&filename).exists() {
+                let description = std::fs::read_to_string(&filename).context("read", &filename)?;
+                std::fs::remove_file(&filename).context("remove", &filename)?;
+                orga.set_topic_description(&self.topic_name, &description)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        topic_to_json(orga, &self.topic_name)
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        if self.description.is_none() && !self.edit {
+            match orga.get_topic_description(&self.topic_name)? {
+                Some(description) => println!("{}", description),
+                None => println!("No description for topic '{}'.", self.topic_name),
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, StructOpt)]
 struct PathCommand {
+    #[allow(dead_code)]
     #[structopt(help="the name of the topic containing the paths")]
     topic: Option<String>,
     #[structopt(subcommand)]
@@ -160,22 +552,46 @@ enum SubPathCommand {
     Remove(RemovePathCommand),
     #[structopt(about="reset a path to another location")]
     Reset(ResetPathCommand),
+    #[structopt(about="rename a path")]
+    Rename(RenamePathCommand),
+    #[structopt(about="merge a path into another one with a synthesis note")]
+    Merge(MergePathCommand),
+    #[structopt(about="show the history of a path")]
+    Log(LogPathCommand),
+    #[structopt(about="copy a note or a range of notes on top of a path")]
+    CherryPick(CherryPickPathCommand),
+    #[structopt(about="replay the notes of a path on top of another location")]
+    Rebase(RebasePathCommand),
+    #[structopt(about="compare two paths since their common ancestor")]
+    Diff(DiffPathCommand),
 }
 
 impl PathCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
-        let mut orga = Organization::new(Store::attach(base_dir)?);
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
         match &self.subcommand {
             SubPathCommand::List(cmd)
-                => cmd.execute(&mut orga),
+                => cmd.execute(&mut orga, ctx),
             SubPathCommand::Branch(cmd)
-                => cmd.execute(&mut orga),
+                => cmd.execute(&mut orga, ctx),
             SubPathCommand::Default(cmd)
-                => cmd.execute(&mut orga),
+                => cmd.execute(&mut orga, ctx),
             SubPathCommand::Remove(cmd)
-                => cmd.execute(&mut orga),
+                => cmd.execute(&mut orga, ctx),
             SubPathCommand::Reset(cmd)
-                => cmd.execute(&mut orga),
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::Rename(cmd)
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::Merge(cmd)
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::Log(cmd)
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::CherryPick(cmd)
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::Rebase(cmd)
+                => cmd.execute(&mut orga, ctx),
+            SubPathCommand::Diff(cmd)
+                => cmd.execute(&mut orga, ctx),
         }
     }
 }
@@ -188,9 +604,19 @@ struct DefaultPathCommand {
     path: String,
 }
 
-impl DefaultPathCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        orga.set_current_path(self.topic.as_deref(), &self.path)?;
+impl Render for DefaultPathCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        orga.set_current_path(self.topic.as_deref(), &self.path)
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        let topic = self.topic.clone().map_or_else(|| orga.get_current_topic(), |topic| Ok(Some(topic)))?.unwrap_or_default();
+        path_to_json(orga, &topic, &self.path)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
         Ok(())
     }
 }
@@ -201,15 +627,28 @@ struct ListPathCommand {
     topic: Option<String>,
 }
 
-impl ListPathCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let (topic, list) = orga.get_paths_list(self.topic.as_deref())?;
+impl Render for ListPathCommand {
+    type Output = (String, Vec<String>);
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<(String, Vec<String>)> {
+        orga.get_paths_list(self.topic.as_deref())
+    }
+
+    fn json(&self, orga: &mut Organization, (topic, list): &(String, Vec<String>)) -> Result<Value> {
+        let mut paths = Vec::new();
+        for path in list {
+            paths.push(path_to_json(orga, topic, path)?);
+        }
+        Ok(Value::Array(paths))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, (topic, list): &(String, Vec<String>)) -> Result<()> {
         if list.is_empty() {
             println!("No paths in topic '{}'.", topic);
         } else {
-            let current = orga.get_current_path(&topic)?.unwrap_or_else(|| "".to_string());
+            let current = orga.get_current_path(topic)?.unwrap_or_else(|| "".to_string());
             for path in list {
-                println!("{} {}", if path == current { "→" } else { " " }, path);
+                println!("{} {}", if *path == current { "→" } else { " " }, path);
             }
         }
         Ok(())
@@ -224,10 +663,22 @@ struct BranchPathCommand {
     location: Option<String>,
 }
 
-impl BranchPathCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        orga.create_path(&self.new_path, self.location.as_deref())?;
-        
+impl Render for BranchPathCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        orga.create_path(&self.new_path, self.location.as_deref())
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        let location = self.location.as_deref().unwrap_or("HEAD");
+        let topic = orga.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?
+            .topic;
+        path_to_json(orga, &topic, &self.new_path)
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
         Ok(())
     }
 }
@@ -240,180 +691,909 @@ struct RemovePathCommand {
     topic: Option<String>,
 }
 
-impl RemovePathCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let metadata = orga.remove_path(&self.path, self.topic.as_deref())?;
-        println!("path '{}' deleted ({})", self.path, metadata.note_id.to_string()[..8].to_string());
+impl Render for RemovePathCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.remove_path(&self.path, self.topic.as_deref())
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!({ "topic": metadata.topic, "name": self.path, "head": metadata.note_id }))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("path '{}' deleted ({})", self.path, orga.short_uuid(metadata.note_id)?);
 
         Ok(())
     }
 }
 
 #[derive(Debug, StructOpt)]
-struct ResetPathCommand {
+struct RenamePathCommand {
     #[structopt(help="the name of the path")]
     path: String,
-    #[structopt(help="the new location of the path")]
-    location: String,
+    #[structopt(help="the new name of the path")]
+    new_path: String,
     #[structopt(short, long, help="the name of the topic if not the default one")]
     topic: Option<String>,
 }
 
-impl ResetPathCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let (old_metadata, new_metadata) = orga.reset_path(&self.path, self.topic.as_deref(), &self.location)?;
-        println!(
-            "path {} reset at {} (was {})",
-            self.path,
-            &old_metadata.note_id.to_string()[..8],
-            &new_metadata.note_id.to_string()[..8]
-        );
+impl Render for RenamePathCommand {
+    type Output = usize;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<usize> {
+        orga.rename_path(&self.path, &self.new_path, self.topic.as_deref())
+    }
+
+    fn json(&self, orga: &mut Organization, count: &usize) -> Result<Value> {
+        let topic = self.topic.clone().map_or_else(|| orga.get_current_topic(), |topic| Ok(Some(topic)))?.unwrap_or_default();
+        Ok(json!({
+            "path": path_to_json(orga, &topic, &self.new_path)?,
+            "previous_name": self.path,
+            "notes_updated": count,
+        }))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, count: &usize) -> Result<()> {
+        println!("path '{}' renamed to '{}' ({} notes updated)", self.path, self.new_path, count);
+
         Ok(())
     }
 }
 
 #[derive(Debug, StructOpt)]
-enum NoteCommand {
-    #[structopt(about="add a new note")]
-    Add(AddNoteCommand),
-    #[structopt(about="create a reference to a note")]
-    Reference(NoteReferenceCommand),
-    #[structopt(about="display a note")]
-    Show(NoteShowCommand),
+struct MergePathCommand {
+    #[structopt(help="the path to merge")]
+    source: String,
+    #[structopt(long, help="the path receiving the merge note if not the default one")]
+    into: Option<String>,
+    #[structopt(short, long, help="the name of the topic if not the default one")]
+    topic: Option<String>,
+    #[structopt(help="content of the merge note, $EDITOR is launched if not given")]
+    filename: Option<String>,
 }
 
-impl NoteCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
-        let mut orga = Organization::new(Store::attach(base_dir)?);
-        match self {
-            NoteCommand::Add(cmd)
-                            => cmd.execute(&mut orga),
-            NoteCommand::Reference(cmd)
-                            => cmd.execute(&mut orga),
-            NoteCommand::Show(cmd)
-                            => cmd.execute(&mut orga),
+impl Render for MergePathCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, ctx: &Context) -> Result<NoteMetaData> {
+        let target = match (self.into.as_ref(), self.topic.clone().map_or_else(|| orga.get_current_topic(), |topic| Ok(Some(topic)))?) {
+            (Some(path), _) => path.clone(),
+            (None, Some(topic)) => orga.get_current_path(&topic)?.unwrap_or_default(),
+            (None, None) => String::new(),
+        };
+        match self.filename.as_ref() {
+            Some(filename) => orga.merge_path(&self.source, self.into.as_deref(), self.topic.as_deref(), filename),
+            None => {
+                let message = format!("Merge path '{}' into '{}'.\n", self.source, target);
+                let filename = ctx.edit(&message)?;
+                // the editor leaves the message unchanged when quitting without saving
+                if std::fs::read_to_string(&filename).map_or(true, |content| content == message) {
+                    let _ = std::fs::remove_file(&filename);
+                    return Err(ZtlnError::Default("Merge note unchanged, aborting operation.".to_string()));
+                }
+                let result = orga.merge_path(&self.source, self.into.as_deref(), self.topic.as_deref(), &filename);
+                if std::path::Path::new(&filename).exists() {
+                    std::fs::remove_file(&filename)?;
+                }
+                result
+            },
         }
     }
-}
 
-#[derive(Debug, StructOpt)]
-struct NoteShowCommand {
-    location: String,
-}
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
 
-impl NoteShowCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let metadata = orga.solve_location(&self.location)?
-            .ok_or_else(|| ZtlnError::LocationError(self.location.to_string()))?;
-        let content = orga.get_note_content(metadata.note_id)?;
-        println!("{}", content);
-        println!("================================================================================");
-        println!("{}", metadata);
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("path '{}' merged into '{}' ({})", self.source, metadata.path, orga.short_uuid(metadata.note_id)?);
 
         Ok(())
     }
 }
 
 #[derive(Debug, StructOpt)]
-struct NoteReferenceCommand {
-    from_location: String,
-    to_location: String,
+struct LogPathCommand {
+    #[structopt(help="the name of the path if not the default one")]
+    path: Option<String>,
+    #[structopt(short, long, help="the name of the topic if not the default one")]
+    topic: Option<String>,
 }
 
-impl NoteReferenceCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        orga.add_note_reference(&self.from_location, &self.to_location)?;
-        Ok(())
+impl Render for LogPathCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.get_path_log(self.path.as_deref(), self.topic.as_deref())
     }
-}
 
-#[derive(Debug, StructOpt)]
-struct AddNoteCommand {
-    filename: Option<String>,
-    #[structopt(long,short,help="set the current topic prior to add the note")]
-    topic: Option<String>,
-    #[structopt(long,short,help="set the current path prior to add the note")]
-    path: Option<String>,
-}
+    fn json(&self, _orga: &mut Organization, log: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!(log))
+    }
 
-impl AddNoteCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let filename = match self.filename.as_ref() {
-            Some(f) => f.clone(),
-            None => {
-                let pathbuf = env::temp_dir().join(rand::thread_rng()
-                    .sample_iter(&Alphanumeric)
-                    .take(10)
-                    .collect::<String>()); 
-                let f = pathbuf.to_str().unwrap();
-                Command::new(env::var_os("EDITOR").unwrap_or_else(|| From::from("vi".to_string())))
-                    .arg(f)
-                    .status()?;
-                f.to_string()
+    fn text(&self, orga: &mut Organization, ctx: &Context, log: &Vec<NoteMetaData>) -> Result<()> {
+        let mut output = String::new();
+        for metadata in log {
+            let date = metadata.created_at.map_or_else(|| " ".repeat(10), |date| date.format("%Y-%m-%d").to_string());
+            write!(output, "{} {} {}", ctx.paint(&orga.short_uuid(metadata.note_id)?), date, orga.get_title(metadata)?).unwrap();
+            if metadata.parents.len() > 1 {
+                let mut parents = Vec::new();
+                for parent in &metadata.parents {
+                    parents.push(orga.short_uuid(*parent)?);
+                }
+                write!(output, " (merge of {})", parents.join(" ")).unwrap();
             }
-        };
-        let meta = orga.add_note(&filename, self.topic.as_deref(), self.path.as_deref())?;
-        let note_id = meta.note_id.to_string();
-        let parent_id = meta.parent_id.map_or_else(|| "".to_string(), |v| v.to_string());
-        println!("Note '{}' ← '{}' added at {}/{}", parent_id, note_id, meta.topic, meta.path);
-        std::fs::remove_file(filename)?;
+            writeln!(output).unwrap();
+        }
 
-        Ok(())
+        ctx.page(&output)
     }
 }
 
 #[derive(Debug, StructOpt)]
-enum TagCommand {
-    #[structopt(about="add a keyword to a note at given location (or HEAD)")]
-    Add(TagAddCommand),
-    #[structopt(about="get the list of notes that are associated with the given keyword")]
-    Search(TagSearchCommand),
-    #[structopt(about="list keywords from the index")]
-    List(TagListCommand),
+struct CherryPickPathCommand {
+    #[structopt(help="a location or a range FROM..TO of notes reachable from TO but not from FROM")]
+    range: String,
+    #[structopt(long, default_value="HEAD", help="the target topic/path")]
+    to: String,
 }
 
-impl TagCommand {
-    fn execute(&self, base_dir: &str) -> Result<()> {
-        let mut orga = Organization::new(Store::attach(base_dir)?);
-        match self {
-            TagCommand::Add(cmd) => cmd.execute(&mut orga),
-            TagCommand::Search(cmd) => cmd.execute(&mut orga),
-            TagCommand::List(cmd) => cmd.execute(&mut orga)
-        }
+impl Render for CherryPickPathCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.cherry_pick(&self.range, &self.to)
     }
-}
 
-#[derive(Debug, StructOpt)]
-struct TagAddCommand {
-    #[structopt(help="the keyword to tag the note with")]
-    keyword: String,
-    #[structopt(help="note's location (defaults to MAIN)")]
-    location: Option<String>,
-}
+    fn json(&self, _orga: &mut Organization, picked: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!(picked))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, picked: &Vec<NoteMetaData>) -> Result<()> {
+        for metadata in picked {
+            let origin = metadata.origin.map_or_else(|| Ok(String::new()), |uuid| orga.short_uuid(uuid))?;
+            println!("Note '{}' ← '{}' picked at {}/{}", origin, orga.short_uuid(metadata.note_id)?, metadata.topic, metadata.path);
+        }
 
-impl TagAddCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        orga.add_keyword(&self.keyword, self.location.as_deref())?;
         Ok(())
     }
 }
 
 #[derive(Debug, StructOpt)]
-struct TagSearchCommand {
-    #[structopt(help="keyword to search in the index")]
+struct RebasePathCommand {
+    #[structopt(help="the name of the path")]
+    path: String,
+    #[structopt(long, help="the location to replay the notes of the path on")]
+    onto: String,
+    #[structopt(short, long, help="the name of the topic if not the default one")]
+    topic: Option<String>,
+}
+
+impl Render for RebasePathCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.rebase_path(&self.path, self.topic.as_deref(), &self.onto)
+    }
+
+    fn json(&self, _orga: &mut Organization, rebased: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!(rebased))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, rebased: &Vec<NoteMetaData>) -> Result<()> {
+        println!("path '{}' rebased onto '{}' ({} notes replayed)", self.path, self.onto, rebased.len());
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct DiffPathCommand {
+    #[structopt(help="the first path or location")]
+    left: String,
+    #[structopt(help="the second path or location")]
+    right: String,
+    #[structopt(short, long, help="show a unified diff of the notes contents")]
+    content: bool,
+}
+
+impl Render for DiffPathCommand {
+    type Output = PathDiff;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<PathDiff> {
+        orga.diff_paths(&self.left, &self.right)
+    }
+
+    fn json(&self, orga: &mut Organization, diff: &PathDiff) -> Result<Value> {
+        let mut value = json!(diff);
+        if self.content {
+            value["content"] = json!(orga.diff_content(diff, &self.left, &self.right)?);
+        }
+        Ok(value)
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, diff: &PathDiff) -> Result<()> {
+        if self.content {
+            print!("{}", orga.diff_content(diff, &self.left, &self.right)?);
+            return Ok(());
+        }
+        match diff.base.as_ref() {
+            Some(base) => println!("common ancestor: {}", orga.short_uuid(base.note_id)?),
+            None => println!("common ancestor: none"),
+        }
+        for (sign, name, notes) in [("-", &self.left, &diff.left), ("+", &self.right, &diff.right)] {
+            println!("{} notes only in '{}':", notes.len(), name);
+            for metadata in notes {
+                println!("{} {} {}", sign, orga.short_uuid(metadata.note_id)?, orga.get_title(metadata)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct ResetPathCommand {
+    #[structopt(help="the name of the path")]
+    path: String,
+    #[structopt(help="the new location of the path")]
+    location: String,
+    #[structopt(short, long, help="the name of the topic if not the default one")]
+    topic: Option<String>,
+}
+
+impl Render for ResetPathCommand {
+    type Output = (NoteMetaData, NoteMetaData);
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<(NoteMetaData, NoteMetaData)> {
+        orga.reset_path(&self.path, self.topic.as_deref(), &self.location)
+    }
+
+    fn json(&self, orga: &mut Organization, (old_metadata, _): &(NoteMetaData, NoteMetaData)) -> Result<Value> {
+        Ok(json!({
+            "path": path_to_json(orga, &old_metadata.topic, &self.path)?,
+            "previous_head": old_metadata.note_id,
+        }))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, (old_metadata, new_metadata): &(NoteMetaData, NoteMetaData)) -> Result<()> {
+        println!(
+            "path {} reset at {} (was {})",
+            self.path,
+            orga.short_uuid(new_metadata.note_id)?,
+            orga.short_uuid(old_metadata.note_id)?
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum NoteCommand {
+    #[structopt(about="add a new note")]
+    Add(AddNoteCommand),
+    #[structopt(about="create a reference to a note")]
+    Reference(NoteReferenceCommand),
+    #[structopt(about="replace the content of a note")]
+    Edit(NoteEditCommand),
+    #[structopt(about="display a note")]
+    Show(NoteShowCommand),
+    #[structopt(about="list all the locations reaching a note")]
+    Where(NoteWhereCommand),
+    #[structopt(about="move a note to another topic or path")]
+    Move(NoteMoveCommand),
+    #[structopt(about="copy a note to another topic or path")]
+    Copy(NoteCopyCommand),
+    #[structopt(about="list the fleeting notes which are not promoted yet")]
+    Inbox(NoteInboxCommand),
+    #[structopt(about="turn a fleeting note into a permanent note of a path")]
+    Promote(NotePromoteCommand),
+    #[structopt(about="record the source a note comes from")]
+    Cite(NoteCiteCommand),
+    #[structopt(about="copy a file in the organization and attach it to a note")]
+    Attach(NoteAttachCommand),
+    #[structopt(about="check or prune the files attached to the notes")]
+    Attachment(NoteAttachmentCommand),
+}
+
+impl NoteCommand {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
+        let result = match self {
+            NoteCommand::Add(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Reference(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Edit(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Show(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Where(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Move(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Copy(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Inbox(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Promote(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Cite(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Attach(cmd)
+                            => cmd.execute(&mut orga, ctx),
+            NoteCommand::Attachment(NoteAttachmentCommand::Check(cmd))
+                            => cmd.execute(&mut orga, base_dir, ctx),
+            NoteCommand::Attachment(NoteAttachmentCommand::Prune(cmd))
+                            => cmd.execute(&mut orga, ctx),
+        };
+        for warning in orga.take_warnings() {
+            ctx.warn(&warning);
+        }
+
+        result
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteShowCommand {
+    location: String,
+}
+
+impl Render for NoteShowCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.solve_location(&self.location)?
+            .ok_or_else(|| ZtlnError::LocationError(self.location.to_string()))
+    }
+
+    fn json(&self, orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        note_to_json(orga, metadata)
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        let content = orga.get_note_content(metadata.note_id)?;
+        let mut output = String::new();
+        writeln!(output, "{}", content).unwrap();
+        writeln!(output, "================================================================================").unwrap();
+        writeln!(output, "title:     {}", orga.get_title(metadata)?).unwrap();
+        writeln!(output, "kind:      {}{}", metadata.kind.as_str(), if metadata.archived { " (promoted)" } else { "" }).unwrap();
+        if let Some(citation) = orga.get_citation(metadata)? {
+            writeln!(output, "source:    {}", citation).unwrap();
+        }
+        writeln!(output, "location:  {}", orga.locate_note(metadata)?.unwrap_or_else(|| "none".to_string())).unwrap();
+        writeln!(output, "note_id:   {}", ctx.paint(&orga.short_uuid(metadata.note_id)?)).unwrap();
+        let mut parents = Vec::new();
+        for parent in &metadata.parents {
+            parents.push(orga.short_uuid(*parent)?);
+        }
+        writeln!(output, "parents:   {}", if parents.is_empty() { "none".to_string() } else { parents.join(" ") }).unwrap();
+        if let Some(date) = metadata.created_at {
+            writeln!(output, "created:   {}", date.format("%Y-%m-%d %H:%M:%S")).unwrap();
+        }
+        if let Some(origin) = metadata.origin {
+            writeln!(output, "origin:    {}", orga.short_uuid(origin)?).unwrap();
+        }
+        writeln!(output, "references:").unwrap();
+        for reference in &metadata.references {
+            writeln!(output, "  - {}", orga.short_uuid(*reference)?).unwrap();
+        }
+        if !metadata.attachments.is_empty() {
+            writeln!(output, "attachments:").unwrap();
+            for path in orga.get_attachment_paths(metadata) {
+                writeln!(output, "  - {}", path.display()).unwrap();
+            }
+        }
+
+        ctx.page(&output)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteEditCommand {
+    #[structopt(default_value="HEAD", help="the note's location or UUID")]
+    location: String,
+    #[structopt(help="file with the new content, $EDITOR is launched with the current content if not given")]
+    filename: Option<String>,
+}
+
+impl Render for NoteEditCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, ctx: &Context) -> Result<NoteMetaData> {
+        match self.filename.as_ref() {
+            Some(filename) => orga.edit_note(&self.location, filename),
+            None => {
+                let metadata = orga.solve_location(&self.location)?
+                    .ok_or_else(|| ZtlnError::LocationError(self.location.to_string()))?;
+                let filename = ctx.edit(&orga.get_note_content(metadata.note_id)?)?;
+                let result = orga.edit_note(&metadata.note_id.to_string(), &filename);
+                if std::path::Path::new(&filename).exists() {
+                    std::fs::remove_file(&filename)?;
+                }
+                result
+            },
+        }
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("Note '{}' updated", orga.short_uuid(metadata.note_id)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteMoveCommand {
+    #[structopt(help="the note's location or UUID")]
+    location: String,
+    #[structopt(long, help="the target topic/path, the note becomes its head")]
+    to: String,
+}
+
+impl Render for NoteMoveCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.move_note(&self.location, &self.to)
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("Note '{}' moved to {}/{}", orga.short_uuid(metadata.note_id)?, metadata.topic, metadata.path);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteCopyCommand {
+    #[structopt(help="the note's location or UUID")]
+    location: String,
+    #[structopt(long, help="the target topic/path, the copy becomes its head")]
+    to: String,
+}
+
+impl Render for NoteCopyCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.copy_note(&self.location, &self.to)
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("Note '{}' copied to {}/{}", orga.short_uuid(metadata.note_id)?, metadata.topic, metadata.path);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteInboxCommand {
+}
+
+impl Render for NoteInboxCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.get_inbox()
+    }
+
+    fn json(&self, _orga: &mut Organization, inbox: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!(inbox))
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, inbox: &Vec<NoteMetaData>) -> Result<()> {
+        if inbox.is_empty() {
+            println!("The inbox is empty.");
+        }
+        for metadata in inbox {
+            let date = metadata.created_at.map_or_else(|| " ".repeat(10), |date| date.format("%Y-%m-%d").to_string());
+            println!("{} {} {}", ctx.paint(&orga.short_uuid(metadata.note_id)?), date, orga.get_title(metadata)?);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NotePromoteCommand {
+    #[structopt(help="the fleeting note's UUID or location")]
+    location: String,
+    #[structopt(long, help="the target topic/path (the current path by default), the new note becomes its head")]
+    to: Option<String>,
+}
+
+impl Render for NotePromoteCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.promote_note(&self.location, self.to.as_deref())
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("Note '{}' promoted to {}/{}", orga.short_uuid(metadata.note_id)?, metadata.topic, metadata.path);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteCiteCommand {
+    #[structopt(help="the note's location or UUID")]
+    location: String,
+    #[structopt(help="citekey of an imported source")]
+    citekey: String,
+    #[structopt(long, help="page (or pages) of the source")]
+    page: Option<String>,
+}
+
+impl Render for NoteCiteCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.cite_source(&self.location, &self.citekey, self.page.as_deref())
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, metadata: &NoteMetaData) -> Result<()> {
+        println!("Note '{}' cites {}", orga.short_uuid(metadata.note_id)?, orga.get_citation(metadata)?.unwrap_or_default());
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteAttachCommand {
+    #[structopt(help="the note's location or UUID")]
+    location: String,
+    #[structopt(help="the file to attach, a file with the same name is replaced")]
+    filename: String,
+}
+
+impl Render for NoteAttachCommand {
+    type Output = (NoteMetaData, String);
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<(NoteMetaData, String)> {
+        orga.attach_file(&self.location, &self.filename)
+    }
+
+    fn json(&self, _orga: &mut Organization, (metadata, _): &(NoteMetaData, String)) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, (metadata, name): &(NoteMetaData, String)) -> Result<()> {
+        println!("File '{}' attached to note '{}'", name, orga.short_uuid(metadata.note_id)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum NoteAttachmentCommand {
+    #[structopt(about="list the attachments missing from the organization, the attached files no note lists and the attachments of deleted notes")]
+    Check(AttachmentCheckCommand),
+    #[structopt(about="remove the attached files no note lists and the attachments of deleted notes")]
+    Prune(AttachmentPruneCommand),
+}
+
+#[derive(Debug, StructOpt)]
+struct AttachmentCheckCommand {}
+
+impl AttachmentCheckCommand {
+    /**
+     * The problems are printed then reported as an error, so that the exit
+     * code tells whether there are some.
+     */
+    fn execute(&self, orga: &mut Organization, base_dir: &str, ctx: &Context) -> Result<()> {
+        let problems = self.run(orga, ctx)?;
+        self.render(orga, ctx, &problems)?;
+        match problems.len() {
+            0 => Ok(()),
+            count => Err(ZtlnError::Corrupted(std::path::Path::new(base_dir).join("attachments"), format!("{} problems found", count))),
+        }
+    }
+}
+
+impl Render for AttachmentCheckCommand {
+    type Output = Vec<AttachmentProblem>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<AttachmentProblem>> {
+        orga.check_attachments()
+    }
+
+    fn json(&self, _orga: &mut Organization, problems: &Vec<AttachmentProblem>) -> Result<Value> {
+        Ok(json!(problems))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, problems: &Vec<AttachmentProblem>) -> Result<()> {
+        for problem in problems {
+            println!("{}", problem);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct AttachmentPruneCommand {
+    #[structopt(long, help="only list what would be removed")]
+    dry_run: bool,
+}
+
+impl Render for AttachmentPruneCommand {
+    type Output = Vec<AttachmentProblem>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<AttachmentProblem>> {
+        orga.prune_attachments(self.dry_run)
+    }
+
+    fn json(&self, _orga: &mut Organization, removed: &Vec<AttachmentProblem>) -> Result<Value> {
+        Ok(json!(removed))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, removed: &Vec<AttachmentProblem>) -> Result<()> {
+        let (verb, total) = match self.dry_run {
+            true => ("would remove", "attachments would be removed"),
+            false => ("removed", "attachments removed"),
+        };
+        for problem in removed {
+            println!("{}: {}", verb, problem);
+        }
+        println!("{} {}", removed.len(), total);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteWhereCommand {
+    #[structopt(help="the note's location or UUID")]
+    location: String,
+}
+
+impl Render for NoteWhereCommand {
+    type Output = (NoteMetaData, Vec<String>);
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<(NoteMetaData, Vec<String>)> {
+        let metadata = orga.solve_location(&self.location)?
+            .ok_or_else(|| ZtlnError::LocationError(self.location.to_string()))?;
+        let locations = orga.get_note_locations(&metadata)?;
+
+        Ok((metadata, locations))
+    }
+
+    fn json(&self, _orga: &mut Organization, (_, locations): &(NoteMetaData, Vec<String>)) -> Result<Value> {
+        Ok(json!(locations))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, (metadata, locations): &(NoteMetaData, Vec<String>)) -> Result<()> {
+        if locations.is_empty() {
+            println!("Note '{}' is not reachable from any path.", orga.short_uuid(metadata.note_id)?);
+        }
+        for location in locations {
+            println!("{}", location);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct NoteReferenceCommand {
+    from_location: String,
+    to_location: String,
+}
+
+impl Render for NoteReferenceCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<NoteMetaData> {
+        orga.add_note_reference(&self.from_location, &self.to_location)
+    }
+
+    fn json(&self, _orga: &mut Organization, metadata: &NoteMetaData) -> Result<Value> {
+        Ok(json!(metadata))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _metadata: &NoteMetaData) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct AddNoteCommand {
+    #[structopt(help="file containing the note, kept once added, `-` reads the standard input")]
+    filename: Option<String>,
+    #[structopt(long,conflicts_with="filename",help="start the note from the given template")]
+    template: Option<String>,
+    #[structopt(long,short,conflicts_with_all=&["filename", "template"],help="the content of the note")]
+    message: Option<String>,
+    #[structopt(long,short,help="set the current topic prior to add the note")]
+    topic: Option<String>,
+    #[structopt(long,short,help="set the current path prior to add the note")]
+    path: Option<String>,
+    #[structopt(long,possible_values=&["fleeting", "literature", "permanent"],help="kind of the note, fleeting notes go to the inbox")]
+    kind: Option<NoteKind>,
+}
+
+impl AddNoteCommand {
+    /**
+     * Find the file holding the content of the note, the UUID of the note when
+     * it comes from a template and whether the file was created here, in which
+     * case it is removed once the note is added.
+     */
+    fn source(&self, orga: &mut Organization, ctx: &Context, stdin: &mut dyn Read) -> Result<(Option<Uuid>, String, bool)> {
+        let source = match (self.filename.as_deref(), self.template.as_ref()) {
+            (Some("-"), _) => {
+                let mut content = String::new();
+                stdin.read_to_string(&mut content)?;
+                (None, write_temp_file(&content)?, true)
+            },
+            (Some(f), _) => (None, f.to_string(), false),
+            (None, _) if self.message.is_some() => {
+                let message = self.message.as_deref().unwrap_or("").trim_end();
+                let content = if message.is_empty() { String::new() } else { format!("{}\n", message) };
+                (None, write_temp_file(&content)?, true)
+            },
+            (None, Some(name)) => {
+                let (note_id, template) = orga.render_template(name, self.topic.as_deref(), self.path.as_deref())?;
+                let filename = ctx.edit(&template)?;
+                // an unchanged template is as empty as an empty file
+                if std::fs::read_to_string(&filename).map_or(true, |content| content == template) {
+                    let _ = std::fs::remove_file(&filename);
+                    return Err(ZtlnError::Default("Note is empty, aborting operation.".to_string()));
+                }
+                (Some(note_id), filename, true)
+            },
+            (None, None) => (None, ctx.edit("")?, true),
+        };
+
+        Ok(source)
+    }
+
+    /**
+     * Add the note from its source, the file is removed even if the note is
+     * refused when it was created here.
+     */
+    fn add(&self, orga: &mut Organization, (note_id, filename, temporary): (Option<Uuid>, String, bool)) -> Result<NoteMetaData> {
+        let kind = self.kind.unwrap_or_default();
+        let result = match (kind, note_id) {
+            (NoteKind::Fleeting, _) => orga.add_fleeting_note(&filename),
+            (_, Some(note_id)) => orga.add_note_with_id(note_id, &filename, self.topic.as_deref(), self.path.as_deref()),
+            (_, None) => orga.add_note(&filename, self.topic.as_deref(), self.path.as_deref()),
+        };
+        if temporary && std::path::Path::new(&filename).exists() {
+            std::fs::remove_file(&filename)?;
+        }
+        let mut meta = result?;
+        if kind == NoteKind::Literature {
+            orga.set_note_kind(&mut meta, kind)?;
+        }
+
+        Ok(meta)
+    }
+}
+
+impl Render for AddNoteCommand {
+    type Output = NoteMetaData;
+
+    fn run(&self, orga: &mut Organization, ctx: &Context) -> Result<NoteMetaData> {
+        if self.kind == Some(NoteKind::Fleeting) && (self.topic.is_some() || self.path.is_some() || self.template.is_some()) {
+            return Err(ZtlnError::Default("Fleeting notes are added to the inbox, without topic, path or template.".to_string()));
+        }
+        let source = self.source(orga, ctx, &mut std::io::stdin())?;
+
+        self.add(orga, source)
+    }
+
+    fn json(&self, _orga: &mut Organization, meta: &NoteMetaData) -> Result<Value> {
+        Ok(json!(meta))
+    }
+
+    fn text(&self, orga: &mut Organization, _ctx: &Context, meta: &NoteMetaData) -> Result<()> {
+        if meta.kind == NoteKind::Fleeting {
+            println!("Note '{}' added to the inbox", orga.get_place(meta)?);
+            return Ok(());
+        }
+        let note_id = meta.note_id.to_string();
+        let parent_id = meta.parent_id().map_or_else(|| "".to_string(), |v| v.to_string());
+        println!("Note '{}' ← '{}' added at {}/{}", parent_id, note_id, meta.topic, meta.path);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum TagCommand {
+    #[structopt(about="add a keyword to a note at given location (or HEAD)")]
+    Add(TagAddCommand),
+    #[structopt(about="get the list of notes that are associated with the given keyword")]
+    Search(TagSearchCommand),
+    #[structopt(about="list keywords from the index")]
+    List(TagListCommand),
+}
+
+impl TagCommand {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
+        match self {
+            TagCommand::Add(cmd) => cmd.execute(&mut orga, ctx),
+            TagCommand::Search(cmd) => cmd.execute(&mut orga, ctx),
+            TagCommand::List(cmd) => cmd.execute(&mut orga, ctx)
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct TagAddCommand {
+    #[structopt(help="the keyword to tag the note with")]
     keyword: String,
+    #[structopt(help="note's location (defaults to MAIN)")]
+    location: Option<String>,
+}
+
+impl Render for TagAddCommand {
+    type Output = ();
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<()> {
+        orga.add_keyword(&self.keyword, self.location.as_deref())
+    }
+
+    fn json(&self, orga: &mut Organization, _: &()) -> Result<Value> {
+        let location = self.location.as_deref().unwrap_or("HEAD");
+        Ok(json!({ "keyword": self.keyword, "note": orga.solve_location(location)? }))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, _: &()) -> Result<()> {
+        Ok(())
+    }
 }
 
-impl TagSearchCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        let list = orga.search_keyword(&self.keyword);
-        for note in &list {
-            println!("{}", note.note_id.to_string()[..8].to_string());
+#[derive(Debug, StructOpt)]
+struct TagSearchCommand {
+    #[structopt(help="keyword to search in the index")]
+    keyword: String,
+}
+
+impl Render for TagSearchCommand {
+    type Output = Vec<NoteMetaData>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<NoteMetaData>> {
+        orga.search_keyword(&self.keyword)
+    }
+
+    fn json(&self, _orga: &mut Organization, list: &Vec<NoteMetaData>) -> Result<Value> {
+        Ok(json!(list))
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, list: &Vec<NoteMetaData>) -> Result<()> {
+        for note in list {
+            println!("{} {} {}", ctx.paint(&orga.short_uuid(note.note_id)?), orga.get_place(note)?, orga.get_title(note)?);
         }
         if list.is_empty() {
-            println!("{} results found.", list.len());
-        } else {
             println!("No result found.");
+        } else {
+            println!("{} results found.", list.len());
         }
         Ok(())
     }
@@ -423,9 +1603,21 @@ impl TagSearchCommand {
 struct TagListCommand {
 }
 
-impl TagListCommand {
-    fn execute(&self, orga: &mut Organization) -> Result<()> {
-        for (kw, count) in orga.list_keywords() {
+impl Render for TagListCommand {
+    type Output = Vec<(String, usize)>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<(String, usize)>> {
+        orga.list_keywords()
+    }
+
+    fn json(&self, _orga: &mut Organization, keywords: &Vec<(String, usize)>) -> Result<Value> {
+        Ok(keywords.iter()
+            .map(|(keyword, count)| json!({ "keyword": keyword, "count": count }))
+            .collect())
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, keywords: &Vec<(String, usize)>) -> Result<()> {
+        for (kw, count) in keywords {
             println!("{} ({} notes)", kw, count);
         }
 
@@ -433,8 +1625,411 @@ impl TagListCommand {
     }
 }
 
+#[derive(Debug, StructOpt)]
+enum SourceCommand {
+    #[structopt(about="import the entries of a BibTeX file")]
+    Import(SourceImportCommand),
+    #[structopt(about="list the sources")]
+    List(SourceListCommand),
+    #[structopt(about="display a source with the notes citing it")]
+    Show(SourceShowCommand),
+}
+
+impl SourceCommand {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        let mut orga = ctx.open(base_dir)?;
+        match self {
+            SourceCommand::Import(cmd) => cmd.execute(&mut orga, ctx),
+            SourceCommand::List(cmd) => cmd.execute(&mut orga, ctx),
+            SourceCommand::Show(cmd) => cmd.execute(&mut orga, ctx),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct SourceImportCommand {
+    #[structopt(help="the BibTeX file")]
+    filename: String,
+}
+
+impl Render for SourceImportCommand {
+    type Output = Vec<Source>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<Source>> {
+        orga.import_sources(&self.filename)
+    }
+
+    fn json(&self, _orga: &mut Organization, sources: &Vec<Source>) -> Result<Value> {
+        Ok(json!(sources))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, sources: &Vec<Source>) -> Result<()> {
+        println!("{} sources imported.", sources.len());
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct SourceListCommand {
+}
+
+impl Render for SourceListCommand {
+    type Output = Vec<Source>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<Source>> {
+        orga.get_sources()
+    }
+
+    fn json(&self, _orga: &mut Organization, sources: &Vec<Source>) -> Result<Value> {
+        Ok(json!(sources))
+    }
+
+    fn text(&self, _orga: &mut Organization, _ctx: &Context, sources: &Vec<Source>) -> Result<()> {
+        for source in sources {
+            println!("{} — {}", source.citekey, source.citation(None));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct SourceShowCommand {
+    citekey: String,
+}
+
+impl Render for SourceShowCommand {
+    type Output = (Source, Vec<NoteMetaData>);
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<(Source, Vec<NoteMetaData>)> {
+        let source = orga.get_source(&self.citekey)?;
+        let notes = orga.get_citing_notes(&self.citekey)?;
+
+        Ok((source, notes))
+    }
+
+    fn json(&self, _orga: &mut Organization, (source, notes): &(Source, Vec<NoteMetaData>)) -> Result<Value> {
+        Ok(json!({ "source": source, "notes": notes }))
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, (source, notes): &(Source, Vec<NoteMetaData>)) -> Result<()> {
+        print!("{}", source.to_bibtex());
+        println!("{} notes citing it:", notes.len());
+        for metadata in notes {
+            let location = orga.locate_note(metadata)?.unwrap_or_else(|| "none".to_string());
+            let page = metadata.page.as_ref().map(|page| format!(" (p. {})", page)).unwrap_or_default();
+            println!("{} {} {}{}", ctx.paint(&orga.short_uuid(metadata.note_id)?), location, orga.get_title(metadata)?, page);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct SearchCommand {
+    #[structopt(required=true, help="words to search in the notes")]
+    query: Vec<String>,
+    #[structopt(long, short, help="only search in this topic")]
+    topic: Option<String>,
+    #[structopt(long, short, help="only search notes reachable from this path")]
+    path: Option<String>,
+    #[structopt(long, help="only search notes tagged with this keyword")]
+    tag: Option<String>,
+    #[structopt(long, short="n", default_value="10", help="maximum number of results")]
+    limit: usize,
+}
+
+impl Render for SearchCommand {
+    type Output = Vec<SearchResult>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<SearchResult>> {
+        let mut results = orga.search(&self.query.join(" "), self.topic.as_deref(), self.path.as_deref(), self.tag.as_deref())?;
+        results.truncate(self.limit);
+
+        Ok(results)
+    }
+
+    fn json(&self, _orga: &mut Organization, results: &Vec<SearchResult>) -> Result<Value> {
+        Ok(json!(results))
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, results: &Vec<SearchResult>) -> Result<()> {
+        if results.is_empty() {
+            println!("No result found.");
+        }
+        for result in results {
+            println!(
+                "{} {} {} ({:.2})",
+                ctx.paint(&orga.short_uuid(result.metadata.note_id)?),
+                orga.get_place(&result.metadata)?,
+                orga.get_title(&result.metadata)?,
+                result.score
+            );
+            println!("    {}", result.snippet);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct GrepCommand {
+    #[structopt(help="regular expression to look for in each line of the notes")]
+    pattern: String,
+    #[structopt(short, long, help="case insensitive matching")]
+    ignore_case: bool,
+    #[structopt(short="l", long, help="only print the locations of the matching notes")]
+    files_with_matches: bool,
+    #[structopt(short, long, help="only print the number of matching lines per note")]
+    count: bool,
+    #[structopt(long, short, help="only search in this topic")]
+    topic: Option<String>,
+}
+
+impl Render for GrepCommand {
+    type Output = Vec<GrepResult>;
+
+    fn run(&self, orga: &mut Organization, _ctx: &Context) -> Result<Vec<GrepResult>> {
+        orga.grep(&self.pattern, self.ignore_case, self.topic.as_deref())
+    }
+
+    /**
+     * As in text, `-l` gives the locations only and `-c` the number of
+     * matching lines instead of the lines.
+     */
+    fn json(&self, _orga: &mut Organization, results: &Vec<GrepResult>) -> Result<Value> {
+        if self.files_with_matches {
+            return Ok(json!(results.iter().map(|result| &result.location).collect::<Vec<&String>>()));
+        }
+        if self.count {
+            return Ok(results.iter()
+                .map(|result| json!({ "note_id": result.metadata.note_id, "location": result.location, "count": result.lines.len() }))
+                .collect());
+        }
+        Ok(json!(results))
+    }
+
+    fn text(&self, orga: &mut Organization, ctx: &Context, results: &Vec<GrepResult>) -> Result<()> {
+        for result in results {
+            if self.files_with_matches {
+                println!("{}", result.location);
+            } else if self.count {
+                println!("{}:{}", result.location, result.lines.len());
+            } else {
+                for (number, line) in &result.lines {
+                    println!("{} {}:{}: {}", ctx.paint(&orga.short_uuid(result.metadata.note_id)?), result.location, number, line);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct ServeCommand {
+    #[structopt(short, long, default_value="8080", help="port to listen on")]
+    port: u16,
+    #[structopt(long, default_value="127.0.0.1", help="address to bind")]
+    bind: String,
+    #[structopt(long, help="refuse the requests modifying the organization")]
+    read_only: bool,
+}
+
+impl ServeCommand {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        match ctx.format {
+            Format::Json => print_json(json!({ "address": self.bind, "port": self.port, "read_only": self.read_only }))?,
+            Format::Text => println!("Serving '{}' on http://{}:{}{}", base_dir, self.bind, self.port, if self.read_only { " (read-only)" } else { "" }),
+        }
+        serve(base_dir, &ctx.config, &self.bind, self.port, self.read_only)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct TuiCommand {}
+
+impl TuiCommand {
+    fn execute(&self, base_dir: &str, ctx: &Context) -> Result<()> {
+        if ctx.format == Format::Json {
+            return Err(ZtlnError::Default("The terminal interface has no JSON output.".to_string()));
+        }
+        tui(ctx.open(base_dir)?, &|content| ctx.edit(content))
+    }
+}
+
+/**
+ * A new file name in the temporary directory.
+ */
+fn temp_filename() -> String {
+    env::temp_dir().join(rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(10)
+        .collect::<String>())
+        .to_string_lossy()
+        .to_string()
+}
+
+/**
+ * Write the given content in a new temporary file and return its name.
+ */
+fn write_temp_file(content: &str) -> Result<String> {
+    let filename = temp_filename();
+    std::fs::write(&filename, content)?;
+
+    Ok(filename)
+}
+
+fn print_json(value: Value) -> Result<()> {
+    println!("{:#}", value);
+    Ok(())
+}
+
+/**
+ * Each kind of error has its own exit code so scripts can react to them.
+ */
+fn exit_code(kind: ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::Invalid => 1,
+        ErrorKind::NotFound => 2,
+        ErrorKind::Conflict => 3,
+        ErrorKind::Parse => 4,
+        ErrorKind::Corrupted => 5,
+        ErrorKind::Io => 6,
+    }
+}
+
+fn fail(e: ZtlnError, format: Format) -> ! {
+    match format {
+        Format::Text => eprintln!("ERROR: {}", e),
+        Format::Json => eprintln!("{}", json!({ "error": e.to_string(), "kind": e.kind() })),
+    }
+    std::process::exit(exit_code(e.kind()));
+}
+
+/**
+ * The value of a global option, read before parsing the arguments since the
+ * organization's configuration may define aliases.
+ */
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    args.iter().enumerate().find_map(|(index, arg)| match arg.strip_prefix(&prefix) {
+        Some(value) => Some(value.to_string()),
+        None if arg == name => args.get(index + 1).cloned(),
+        None => None,
+    })
+}
+
 fn main() {
-    MainOpt::from_args()
-        .execute()
-        .unwrap_or_else(|e| { eprintln!("ERROR: {}", e); std::process::exit(1); });
-}
\ No newline at end of file
+    let args: Vec<String> = env::args().collect();
+    let format = option_value(&args, "--format").and_then(|f| Format::from_str(&f).ok()).unwrap_or(Format::Text);
+    let base_dir = option_value(&args, "--base-dir").or_else(|| env::var("ZTLN_BASE_DIR").ok());
+    let config = Config::load(base_dir.as_deref()).unwrap_or_else(|e| fail(e, format));
+    let opt = MainOpt::from_iter_safe(&args)
+        .or_else(|e| match config.expand_alias(&args) {
+            Some(expanded) => MainOpt::from_iter_safe(&expanded),
+            None => Err(e),
+        })
+        .unwrap_or_else(|e| e.exit());
+    let ctx = Context::new(config, opt.format);
+    opt.execute(&ctx)
+        .unwrap_or_else(|e| fail(e, ctx.format));
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_output() {
+        let base_dir = "tmp/ztln_main1";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        let ctx = Context::new(Config::default(), Some(Format::Json));
+
+        let create = CreateTopicCommand::from_iter(&["create", "topic1", "-d", "About"]);
+        create.run(&mut orga, &ctx).unwrap();
+        let topic = create.json(&mut orga, &()).unwrap();
+        assert_eq!(json!({ "name": "topic1", "current": true, "archived": false, "description": "About", "current_path": null }), topic);
+        let list = ListTopicCommand::from_iter(&["list"]);
+        let output = list.run(&mut orga, &ctx).unwrap();
+        assert_eq!(json!([topic]), list.json(&mut orga, &output).unwrap());
+
+        std::fs::write("tmp/test_main1", "one\ntwo one\n").unwrap();
+        let first = orga.add_note("tmp/test_main1", None, None).unwrap();
+        let list = ListPathCommand::from_iter(&["list"]);
+        let output = list.run(&mut orga, &ctx).unwrap();
+        assert_eq!(
+            json!([{ "topic": "topic1", "name": "main", "current": true, "head": first.note_id }]),
+            list.json(&mut orga, &output).unwrap()
+        );
+
+        let show = NoteShowCommand::from_iter(&["show", "HEAD"]);
+        let output = show.run(&mut orga, &ctx).unwrap();
+        let note = show.json(&mut orga, &output).unwrap();
+        assert_eq!("topic1/main", note["location"]);
+        assert_eq!("one\ntwo one\n", note["content"]);
+        assert_eq!(json!(first.note_id), note["metadata"]["note_id"]);
+        assert_eq!("main", note["metadata"]["path"]);
+
+        let grep = |args: &[&str], orga: &mut Organization| {
+            let cmd = GrepCommand::from_iter(args);
+            let output = cmd.run(orga, &ctx).unwrap();
+            cmd.json(orga, &output).unwrap()
+        };
+        let lines = grep(&["grep", "one"], &mut orga);
+        assert_eq!("topic1/main", lines[0]["location"]);
+        assert_eq!(json!([[1, "one"], [2, "two one"]]), lines[0]["lines"]);
+        assert_eq!(json!(["topic1/main"]), grep(&["grep", "-l", "one"], &mut orga));
+        assert_eq!(
+            json!([{ "note_id": first.note_id, "location": "topic1/main", "count": 2 }]),
+            grep(&["grep", "-c", "one"], &mut orga)
+        );
+
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn add_note_sources() {
+        let base_dir = "tmp/ztln_main2";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        let config = Config { editor: Some("cp tmp/test_main2_editor".to_string()), ..Config::default() };
+        let ctx = Context::new(config, None);
+        let exists = |filename: &str| std::path::Path::new(filename).exists();
+        let add = |args: &[&str], input: &str, orga: &mut Organization| {
+            let cmd = AddNoteCommand::from_iter(args);
+            let source = cmd.source(orga, &ctx, &mut input.as_bytes()).unwrap();
+            let filename = source.1.clone();
+            (cmd.add(orga, source), filename)
+        };
+
+        let (meta, filename) = add(&["add", "-"], "from stdin\n", &mut orga);
+        assert_eq!("from stdin\n", orga.get_note_content(meta.unwrap().note_id).unwrap());
+        assert!(!exists(&filename), "standard input is written in a temporary file");
+        let (meta, filename) = add(&["add", "-m", "quick thought"], "", &mut orga);
+        assert_eq!("quick thought\n", orga.get_note_content(meta.unwrap().note_id).unwrap());
+        assert!(!exists(&filename));
+        let (meta, filename) = add(&["add", "-m", ""], "", &mut orga);
+        assert!(meta.is_err());
+        assert!(!exists(&filename), "temporary file removed when the note is refused");
+
+        std::fs::write("tmp/test_main2", "from a file\n").unwrap();
+        let (meta, _) = add(&["add", "tmp/test_main2"], "", &mut orga);
+        assert_eq!("from a file\n", orga.get_note_content(meta.unwrap().note_id).unwrap());
+        assert!(exists("tmp/test_main2"), "given files are kept");
+        std::fs::write("tmp/test_main2", "").unwrap();
+        assert!(add(&["add", "tmp/test_main2"], "", &mut orga).0.is_err());
+        assert!(exists("tmp/test_main2"));
+
+        std::fs::write("tmp/test_main2_editor", "from the editor\n").unwrap();
+        let (meta, filename) = add(&["add"], "", &mut orga);
+        assert_eq!("from the editor\n", orga.get_note_content(meta.unwrap().note_id).unwrap());
+        assert!(!exists(&filename));
+        assert!(exists("tmp/test_main2_editor"));
+        std::fs::create_dir(format!("{}/templates", base_dir)).unwrap();
+        std::fs::write(format!("{}/templates/daily.md", base_dir), "{{date}}\n").unwrap();
+        let (meta, filename) = add(&["add", "--template", "daily"], "", &mut orga);
+        assert_eq!("from the editor\n", orga.get_note_content(meta.unwrap().note_id).unwrap());
+        assert!(!exists(&filename));
+
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+}
@@ -1,47 +1,162 @@
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use crate::error::{ZtlnError, Result};
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+/**
+The steps of a note in the zettelkasten: fleeting notes are quick thoughts
+kept in the inbox, outside any path, until they are promoted into permanent
+notes. Literature notes sum up what was read.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteKind {
+    Fleeting,
+    Literature,
+    #[default]
+    Permanent,
+}
+
+impl NoteKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteKind::Fleeting => "fleeting",
+            NoteKind::Literature => "literature",
+            NoteKind::Permanent => "permanent",
+        }
+    }
+}
+
+impl std::str::FromStr for NoteKind {
+    type Err = ZtlnError;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "fleeting" => Ok(NoteKind::Fleeting),
+            "literature" => Ok(NoteKind::Literature),
+            "permanent" => Ok(NoteKind::Permanent),
+            _ => Err(ZtlnError::ParserError("kind".to_string(), Some(format!("unknown kind '{}'", kind)))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct NoteMetaData {
     pub note_id: Uuid,
-    pub parent_id: Option<Uuid>,
+    pub parents: Vec<Uuid>,
     pub references: Vec<Uuid>,
     pub topic: String,
     pub path: String,
+    pub created_at: Option<DateTime<Utc>>,
+    /// the note this one was copied from
+    pub origin: Option<Uuid>,
+    /// title of the front-matter or first line of the note
+    pub title: Option<String>,
+    pub kind: NoteKind,
+    /// fleeting note already promoted
+    pub archived: bool,
+    /// citekey of the source the note comes from
+    pub source: Option<String>,
+    /// page of the source
+    pub page: Option<String>,
+    /// names of the files attached to the note
+    pub attachments: Vec<String>,
 }
 
 impl fmt::Display for NoteMetaData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "note_id:   {}\n", &self.note_id.to_string()[..8])?;
-        write!(f, "parent_id: {}\n", &self.parent_id.map_or("none    ".to_string(), |uuid| uuid.to_string())[..8].trim().to_string())?;
+        writeln!(f, "note_id:   {}", &self.note_id.to_string()[..8])?;
+        if self.parents.is_empty() {
+            writeln!(f, "parents:   none")?;
+        } else {
+            let parents: Vec<String> = self.parents.iter().map(|uuid| uuid.to_string()[..8].to_string()).collect();
+            writeln!(f, "parents:   {}", parents.join(" "))?;
+        }
+        if let Some(date) = self.created_at {
+            writeln!(f, "created:   {}", date.format("%Y-%m-%d %H:%M:%S"))?;
+        }
+        if let Some(origin) = self.origin {
+            writeln!(f, "origin:    {}", &origin.to_string()[..8])?;
+        }
         write!(f, "references:")?;
         for reference in &self.references {
-            write!(f, "  - {}\n", &reference.to_string()[..8])?;
+            writeln!(f, "  - {}", &reference.to_string()[..8])?;
         }
         write!(f, "")
     }
 }
 
 impl NoteMetaData {
+    /**
+     * The parent followed to go back in the history of a note. A merge note
+     * has several parents, the first one is the head of the path it belongs
+     * to.
+     */
+    pub fn parent_id(&self) -> Option<Uuid> {
+        self.parents.first().copied()
+    }
+
+    /**
+     * The meta file holds the space separated parents ids, the topic and the
+     * path on the first three lines. Each following line is either a reference's UUID or an
+     * optional `key: value` field. Fleeting notes have no topic nor path.
+     */
     pub fn parse_meta_file(uuid: Uuid, content: &str) -> Result<Self> {
         let note_id = uuid;
         let mut lines = content.lines();
-        let parent_id = lines.next().ok_or_else(|| ZtlnError::ParserError("parent_id".to_string(), None))?;
-        let parent_id = if !parent_id.is_empty() { Some(Uuid::parse_str(parent_id)?) } else { None };
+        let parents = lines.next().ok_or_else(|| ZtlnError::ParserError("parents".to_string(), None))?
+            .split_whitespace()
+            .map(Uuid::parse_str)
+            .collect::<std::result::Result<Vec<Uuid>, _>>()
+            .map_err(|e| ZtlnError::ParserError("parents".to_string(), Some(e.to_string())))?;
         let topic = lines.next().ok_or_else(|| ZtlnError::ParserError("topic".to_string(), None))?.to_string();
-        if topic.is_empty() {
-            return Err(From::from(ZtlnError::ParserError("topic".to_string(), Some("field is empty".to_string()))))
-        }
         let path = lines.next().ok_or_else(|| ZtlnError::ParserError("path".to_string(), None))?.to_string();
-        if path.is_empty() {
-            return Err(From::from(ZtlnError::ParserError("path".to_string(), Some("field is empty".to_string()))))
-        }
         let mut references = Vec::new();
-        for reference in lines {
-            references.push(Uuid::parse_str(reference)?);
+        let mut created_at = None;
+        let mut origin = None;
+        let mut title = None;
+        let mut kind = NoteKind::Permanent;
+        let mut archived = false;
+        let mut source = None;
+        let mut page = None;
+        let mut attachments = Vec::new();
+        for line in lines {
+            match line.split_once(": ") {
+                Some(("created_at", value)) => {
+                    created_at = Some(DateTime::parse_from_rfc3339(value)
+                        .map_err(|e| ZtlnError::ParserError("created_at".to_string(), Some(e.to_string())))?
+                        .with_timezone(&Utc));
+                },
+                Some(("origin", value)) => {
+                    origin = Some(Uuid::parse_str(value)
+                        .map_err(|e| ZtlnError::ParserError("origin".to_string(), Some(e.to_string())))?);
+                },
+                Some(("title", value)) => title = Some(value.to_string()),
+                Some(("kind", value)) => kind = value.parse()?,
+                Some(("archived", value)) => {
+                    archived = value.parse()
+                        .map_err(|e: std::str::ParseBoolError| ZtlnError::ParserError("archived".to_string(), Some(e.to_string())))?;
+                },
+                Some(("source", value)) => source = Some(value.to_string()),
+                Some(("page", value)) => page = Some(value.to_string()),
+                Some(("attachment", value)) => attachments.push(value.to_string()),
+                Some((key, _)) => {
+                    return Err(ZtlnError::ParserError(key.to_string(), Some("unknown field".to_string())))
+                },
+                None => references.push(Uuid::parse_str(line)
+                    .map_err(|e| ZtlnError::ParserError("references".to_string(), Some(e.to_string())))?),
+            }
         }
-        Ok(Self { note_id, parent_id, references, topic, path })
+        if kind != NoteKind::Fleeting {
+            if topic.is_empty() {
+                return Err(ZtlnError::ParserError("topic".to_string(), Some("field is empty".to_string())))
+            }
+            if path.is_empty() {
+                return Err(ZtlnError::ParserError("path".to_string(), Some("field is empty".to_string())))
+            }
+        }
+        Ok(Self { note_id, parents, references, topic, path, created_at, origin, title, kind, archived, source, page, attachments })
     }
 
     pub fn serialize(&self) -> String {
@@ -50,21 +165,83 @@ impl NoteMetaData {
             buf.push('\n');
             buf.push_str(&uuid.to_string());
         }
-        let mut content = self.parent_id
-            .map_or("".to_string(), |uuid| uuid.to_string());
+        let mut content = self.parents.iter()
+            .map(|uuid| uuid.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
         content.push('\n');
         content.push_str(&self.topic);
         content.push('\n');
         content.push_str(&self.path);
         content.push_str(&buf);
-        
+        if let Some(date) = self.created_at {
+            content.push_str("\ncreated_at: ");
+            content.push_str(&date.to_rfc3339());
+        }
+        if let Some(origin) = self.origin {
+            content.push_str("\norigin: ");
+            content.push_str(&origin.to_string());
+        }
+        if let Some(title) = self.title.as_ref() {
+            content.push_str("\ntitle: ");
+            content.push_str(title);
+        }
+        if self.kind != NoteKind::Permanent {
+            content.push_str("\nkind: ");
+            content.push_str(self.kind.as_str());
+        }
+        if self.archived {
+            content.push_str("\narchived: true");
+        }
+        if let Some(source) = self.source.as_ref() {
+            content.push_str("\nsource: ");
+            content.push_str(source);
+        }
+        if let Some(page) = self.page.as_ref() {
+            content.push_str("\npage: ");
+            content.push_str(page);
+        }
+        for attachment in &self.attachments {
+            content.push_str("\nattachment: ");
+            content.push_str(attachment);
+        }
+
         content
     }
 }
 
+/**
+A disagreement between the meta data of the notes and the files attached to
+them, found by `check_attachments`.
+ */
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum AttachmentProblem {
+    /// listed in the meta data of the note but not in the organization
+    Missing { note_id: Uuid, name: String },
+    /// in the organization but not listed in the meta data of the note
+    Unlisted { note_id: Uuid, name: String },
+    /// attachments of a note which does not exist anymore
+    Orphaned { note_id: Uuid },
+}
+
+impl fmt::Display for AttachmentProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttachmentProblem::Missing { note_id, name }
+                                => write!(f, "attachment '{}' of note '{}' is missing", name, note_id),
+            AttachmentProblem::Unlisted { note_id, name }
+                                => write!(f, "file '{}' is not attached to note '{}'", name, note_id),
+            AttachmentProblem::Orphaned { note_id }
+                                => write!(f, "attachments of note '{}' which does not exist", note_id),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorKind;
 
     #[test]
     fn parse_meta_file_ok() {
@@ -74,11 +251,11 @@ mod tests {
             let mut f = std::collections::HashMap::new();
             f.insert(
                 "\ntopic\nmain".to_string(),
-                NoteMetaData { note_id, parent_id: None, topic: "topic".to_string(), path: "main".to_string(), references: Vec::new() }
+                NoteMetaData { note_id, parents: Vec::new(), topic: "topic".to_string(), path: "main".to_string(), references: Vec::new(), created_at: None, origin: None, title: None, kind: NoteKind::Permanent, archived: false, source: None, page: None, attachments: Vec::new() }
             );
             f.insert(
                 format!("{}\ntopic\nmain", identifier),
-                NoteMetaData { note_id, parent_id: Some(note_id), topic: "topic".to_string(), path: "main".to_string(), references: Vec::new() }
+                NoteMetaData { note_id, parents: vec![note_id], topic: "topic".to_string(), path: "main".to_string(), references: Vec::new(), created_at: None, origin: None, title: None, kind: NoteKind::Permanent, archived: false, source: None, page: None, attachments: Vec::new() }
             );
 
             f
@@ -98,10 +275,18 @@ mod tests {
     fn serialize_empty() {
         let empty_metadata = NoteMetaData {
             note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
-            parent_id: None,
+            parents: Vec::new(),
             references: Vec::new(),
             topic: "topic1".to_string(),
             path: "main".to_string(),
+            created_at: None,
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
         };
         assert_eq!("\ntopic1\nmain", empty_metadata.serialize());
     }
@@ -111,13 +296,21 @@ mod tests {
             note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
             topic: "topic1".to_string(),
             path: "main".to_string(),
-            parent_id: Some(Uuid::parse_str("0a0aeade-6dc0-407a-8c67-4951ef4ace7f").unwrap()),
+            parents: vec![Uuid::parse_str("0a0aeade-6dc0-407a-8c67-4951ef4ace7f").unwrap()],
             references: vec![
                 Uuid::parse_str("65d436f9-045c-4738-8bdf-d6c3b53ea059").unwrap(),
                 Uuid::parse_str("568acc08-74e5-4ab8-a440-42a206009c5f").unwrap(),
                 Uuid::parse_str("f0707063-e487-4a96-aa64-00bf6aa10e26").unwrap(),
                 Uuid::parse_str("de527948-aeb2-4a91-946a-d0fa231c7a99").unwrap(),
             ],
+            created_at: None,
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
          };
          let content = r"0a0aeade-6dc0-407a-8c67-4951ef4ace7f
 topic1
@@ -135,13 +328,21 @@ de527948-aeb2-4a91-946a-d0fa231c7a99";
             note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
             topic: "topic1".to_string(),
             path: "main".to_string(),
-            parent_id: None,
+            parents: Vec::new(),
             references: vec![
                 Uuid::parse_str("65d436f9-045c-4738-8bdf-d6c3b53ea059").unwrap(),
                 Uuid::parse_str("568acc08-74e5-4ab8-a440-42a206009c5f").unwrap(),
                 Uuid::parse_str("f0707063-e487-4a96-aa64-00bf6aa10e26").unwrap(),
                 Uuid::parse_str("de527948-aeb2-4a91-946a-d0fa231c7a99").unwrap(),
             ],
+            created_at: None,
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
          };
          let content = r"
 topic1
@@ -152,4 +353,104 @@ f0707063-e487-4a96-aa64-00bf6aa10e26
 de527948-aeb2-4a91-946a-d0fa231c7a99";
         assert_eq!(content, metadata.serialize());
      }
-}
\ No newline at end of file
+
+     #[test]
+     fn serialize_created_at() {
+         let metadata = NoteMetaData {
+            note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
+            topic: "topic1".to_string(),
+            path: "main".to_string(),
+            parents: Vec::new(),
+            references: vec![Uuid::parse_str("65d436f9-045c-4738-8bdf-d6c3b53ea059").unwrap()],
+            created_at: Some(DateTime::parse_from_rfc3339("2024-05-01T10:20:30+00:00").unwrap().with_timezone(&Utc)),
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
+         };
+         let content = r"
+topic1
+main
+65d436f9-045c-4738-8bdf-d6c3b53ea059
+created_at: 2024-05-01T10:20:30+00:00";
+        assert_eq!(content, metadata.serialize());
+        assert_eq!(metadata, NoteMetaData::parse_meta_file(metadata.note_id, content).unwrap());
+        assert!(NoteMetaData::parse_meta_file(metadata.note_id, "\ntopic1\nmain\nwhatever: field").is_err());
+        let copy = NoteMetaData { origin: Some(metadata.note_id), ..metadata };
+        let copy_content = format!("{}\norigin: ec511da0-b751-4fee-a10a-e1f83cd34ff8", content);
+        assert_eq!(copy_content, copy.serialize());
+        assert_eq!(copy, NoteMetaData::parse_meta_file(copy.note_id, &copy_content).unwrap());
+        let titled = NoteMetaData { title: Some("Ownership: rules".to_string()), ..copy };
+        let titled_content = format!("{}\ntitle: Ownership: rules", copy_content);
+        assert_eq!(titled_content, titled.serialize());
+        assert_eq!(titled, NoteMetaData::parse_meta_file(titled.note_id, &titled_content).unwrap());
+     }
+
+     #[test]
+     fn fleeting_notes() {
+         let metadata = NoteMetaData {
+            note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
+            topic: String::new(),
+            path: String::new(),
+            parents: Vec::new(),
+            references: Vec::new(),
+            created_at: None,
+            origin: None,
+            title: None,
+            kind: NoteKind::Fleeting,
+            archived: true,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
+         };
+         let content = "\n\n\nkind: fleeting\narchived: true";
+        assert_eq!(content, metadata.serialize());
+        assert_eq!(metadata, NoteMetaData::parse_meta_file(metadata.note_id, content).unwrap());
+        assert!(NoteMetaData::parse_meta_file(metadata.note_id, "\n\n\nkind: literature").is_err());
+        assert!(NoteMetaData::parse_meta_file(metadata.note_id, "\ntopic1\nmain\nkind: draft").is_err());
+        let error = NoteMetaData::parse_meta_file(metadata.note_id, "\n\n\nkind: fleeting\narchived: yes").unwrap_err();
+        assert!(matches!(&error, ZtlnError::ParserError(field, Some(_)) if field == "archived"), "{:?}", error);
+        assert_eq!(ErrorKind::Parse, error.kind());
+        assert_eq!(Ok(NoteKind::Literature), "literature".parse::<NoteKind>().map_err(|e| e.to_string()));
+        let cited = NoteMetaData {
+            kind: NoteKind::Literature,
+            archived: false,
+            source: Some("ahrens2017".to_string()),
+            page: Some("42-43".to_string()),
+            attachments: vec!["figure 1.png".to_string(), "paper.pdf".to_string()],
+            ..metadata
+        };
+        let cited_content = "\n\n\nkind: literature\nsource: ahrens2017\npage: 42-43\nattachment: figure 1.png\nattachment: paper.pdf";
+        assert_eq!(cited_content, cited.serialize());
+        assert!(NoteMetaData::parse_meta_file(cited.note_id, cited_content).is_err(), "only fleeting notes are outside paths");
+     }
+
+     #[test]
+     fn merge_parents() {
+         let parent1 = Uuid::parse_str("0a0aeade-6dc0-407a-8c67-4951ef4ace7f").unwrap();
+         let parent2 = Uuid::parse_str("65d436f9-045c-4738-8bdf-d6c3b53ea059").unwrap();
+         let metadata = NoteMetaData {
+            note_id: Uuid::parse_str("ec511da0-b751-4fee-a10a-e1f83cd34ff8").unwrap(),
+            topic: "topic1".to_string(),
+            path: "main".to_string(),
+            parents: vec![parent1, parent2],
+            references: Vec::new(),
+            created_at: None,
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
+         };
+         let content = "0a0aeade-6dc0-407a-8c67-4951ef4ace7f 65d436f9-045c-4738-8bdf-d6c3b53ea059\ntopic1\nmain";
+        assert_eq!(content, metadata.serialize());
+        assert_eq!(metadata, NoteMetaData::parse_meta_file(metadata.note_id, content).unwrap());
+        assert_eq!(Some(parent1), metadata.parent_id(), "the first parent is followed");
+        assert!(NoteMetaData::parse_meta_file(metadata.note_id, "0a0aeade whatever\ntopic1\nmain").is_err());
+     }
+}
@@ -1,100 +1,287 @@
-use crate::store::{Store, IOStore};
-use crate::error::{ZtlnError, Result};
-use crate::note::NoteMetaData;
-use regex::{Regex, CaptureMatches};
+use crate::store::{Store, IOStore, MIN_SHORT_UUID_LENGTH};
+use crate::error::{ZtlnError, ErrorKind, Result, IoContext};
+use crate::note::{NoteMetaData, NoteKind, AttachmentProblem};
+use crate::search::{SearchResult, GrepResult, make_snippet, title_score};
+use crate::location::{Location, Anchor, Modifier, is_uuid_prefix};
+use crate::diff::{PathDiff, unified_diff};
+use crate::config::Config;
+use crate::bibtex::Source;
+use crate::front_matter::FrontMatter;
+use crate::links::{wiki_links, rewrite_links};
+use regex::{Regex, RegexBuilder};
+use chrono::{SubsecRound, TimeZone, Utc};
 use uuid::Uuid;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+/// name of the first path of a topic when none is given
+pub const DEFAULT_PATH: &str = "main";
+
+/// path reaching a note with the number of the parent taken at each step
+type Route = (String, Vec<usize>);
 
 #[derive(Debug)]
 pub struct Organization<'a> {
     current_topic: Option<String>,
+    default_path: String,
+    templates: HashMap<String, PathBuf>,
+    rewrite_links: bool,
+    /// problems which did not prevent the last operations, see `take_warnings`
+    warnings: Vec<String>,
     store: Store<'a>,
 }
 
+/**
+What the file of a note adds to it: its title, the tags and references of its
+front-matter, the references of its wiki-links and, when links are rewritten,
+the content to store.
+ */
+struct NoteLinks {
+    title: String,
+    tags: Vec<String>,
+    references: Vec<Uuid>,
+    content: Option<String>,
+}
+
 impl<'a> Organization<'a> {
     pub fn new(store: Store<'a>) -> Self {
         Self {
             current_topic: None,
+            default_path: DEFAULT_PATH.to_string(),
+            templates: HashMap::new(),
+            rewrite_links: false,
+            warnings: Vec::new(),
             store
         }
     }
 
-    pub fn get_current_topic(&mut self) -> Option<String> {
+    /**
+     * Return and forget the warnings of the operations done so far.
+     */
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /**
+     * Forget what is kept in memory (current topic, list of notes) so the
+     * changes made by other processes are seen, for long running callers.
+     */
+    pub fn reload(&mut self) {
+        self.current_topic = None;
+        self.store.reload();
+    }
+
+    /**
+     * Apply the preferences of the configuration concerning the organization.
+     */
+    pub fn configure(&mut self, config: &Config) -> Result<()> {
+        if let Some(path) = config.default_path.as_ref() {
+            check_name(path)?;
+            self.default_path = path.to_string();
+        }
+        self.templates = config.templates.clone();
+        self.rewrite_links = config.rewrite_links.unwrap_or(false);
+
+        Ok(())
+    }
+
+    pub fn get_current_topic(&mut self) -> Result<Option<String>> {
         if self.current_topic.is_none() {
-            let topic = self.store
-                .get_current_topic()
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e));
-            if topic == None {
-                return None;
-            }
-            self.current_topic = topic;
+            self.current_topic = self.store.get_current_topic()?;
         }
 
-        (self.current_topic).clone()
+        Ok((self.current_topic).clone())
     }
 
     pub fn set_current_topic(&mut self, topic: &str) -> Result<()> {
         if !self.store.topic_exists(topic) {
-            Err(From::from(ZtlnError::TopicDoesNotExist(topic.to_string())))
+            Err(ZtlnError::TopicDoesNotExist(topic.to_string()))
         } else {
-            self.store.set_current_topic(topic)
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+            self.store.set_current_topic(topic)?;
             self.current_topic = Some(topic.to_string());
             Ok(())
         }
     }
 
     pub fn create_topic(&mut self, topic: &str) -> Result<()> {
+        let main_path = self.default_path.clone();
+        self.create_topic_with_main_path(topic, &main_path)
+    }
+
+    /**
+     * Create a topic which first path, created with the first note, will
+     * have the given name.
+     */
+    pub fn create_topic_with_main_path(&mut self, topic: &str, main_path: &str) -> Result<()> {
         if self.store.topic_exists(topic) {
-            Err(From::from(ZtlnError::TopicAlreadyExists(topic.to_string())))
+            Err(ZtlnError::TopicAlreadyExists(topic.to_string()))
         } else {
-            self.store.create_topic(topic)
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e));
-            if self.get_current_topic().is_none() {
-                self.set_current_topic(topic)
-                    .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+            check_name(topic)?;
+            check_name(main_path)?;
+            self.store.create_topic(topic)?;
+            self.store.set_current_path(topic, main_path)?;
+            if self.get_current_topic()?.is_none() {
+                self.set_current_topic(topic)?;
                 self.current_topic = Some(topic.to_string());
             }
             Ok(())
         }
     }
 
-    pub fn get_topics_list(&self) -> Vec<String> {
-        self.store.get_topics()
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e))
+    /**
+     * List the topics, archived topics are only listed on demand.
+     */
+    pub fn get_topics_list(&self, with_archived: bool) -> Result<Vec<String>> {
+        let mut topics = self.store.get_topics()?;
+        if !with_archived {
+            topics.retain(|topic| !self.store.is_topic_archived(topic));
+        }
+
+        Ok(topics)
     }
 
+    pub fn is_topic_archived(&self, topic: &str) -> bool {
+        self.store.is_topic_archived(topic)
+    }
+
+    pub fn get_topic_description(&self, topic: &str) -> Result<Option<String>> {
+        if !self.store.topic_exists(topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic.to_string()));
+        }
+        self.store.get_topic_description(topic)
+    }
+
+    /**
+     * Set the description of a topic, an empty description removes it.
+     */
+    pub fn set_topic_description(&mut self, topic: &str, description: &str) -> Result<()> {
+        if !self.store.topic_exists(topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic.to_string()));
+        }
+        let description = description.trim();
+        self.store.set_topic_description(topic, Some(description).filter(|d| !d.is_empty()))
+    }
+
+    /**
+     * Rename a topic and update the meta data of all its notes. Return the
+     * number of notes updated.
+     */
+    pub fn rename_topic(&mut self, topic: &str, new_topic: &str) -> Result<usize> {
+        if !self.store.topic_exists(topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic.to_string()));
+        }
+        if self.store.topic_exists(new_topic) {
+            return Err(ZtlnError::TopicAlreadyExists(new_topic.to_string()));
+        }
+        check_name(new_topic)?;
+        self.store.rename_topic(topic, new_topic)?;
+        let mut count = 0;
+        for mut metadata in self.get_topic_notes(topic)? {
+            metadata.topic = new_topic.to_string();
+            self.store.write_note_metadata(&metadata)?;
+            count += 1;
+        }
+        if self.get_current_topic()?.as_deref() == Some(topic) {
+            self.set_current_topic(new_topic)?;
+        }
+
+        Ok(count)
+    }
+
+    /**
+     * Delete a topic and all its notes. If notes of other topics refer to
+     * them, the deletion is refused unless forced. In this case, these
+     * references are removed. Return the deleted notes.
+     */
+    pub fn delete_topic(&mut self, topic: &str, force: bool) -> Result<Vec<NoteMetaData>> {
+        if !self.store.topic_exists(topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic.to_string()));
+        }
+        let notes = self.get_topic_notes(topic)?;
+        let deleted: Vec<Uuid> = notes.iter().map(|meta| meta.note_id).collect();
+        let mut referencing = Vec::new();
+        for uuid in self.store.get_notes()? {
+            if let Some(metadata) = self.store.get_note_metadata(uuid)? {
+                if metadata.topic != topic && metadata.references.iter().any(|r| deleted.contains(r)) {
+                    referencing.push(metadata);
+                }
+            }
+        }
+        if !referencing.is_empty() && !force {
+            let mut list = Vec::new();
+            for metadata in &referencing {
+                list.push(format!("{}/{}", metadata.topic, self.short_uuid(metadata.note_id)?));
+            }
+            return Err(ZtlnError::Conflict(format!(
+                "Notes of topic '{}' are referenced by other notes ({}), use --force to delete it anyway.",
+                topic,
+                list.join(", ")
+            )));
+        }
+        for mut metadata in referencing {
+            metadata.references.retain(|r| !deleted.contains(r));
+            self.store.write_note_metadata(&metadata)?;
+        }
+        self.with_search_index(|orga| {
+            for uuid in deleted {
+                orga.store.remove_note(uuid)?;
+            }
+            Ok(())
+        })?;
+        self.store.remove_topic(topic)?;
+        if self.get_current_topic()?.as_deref() == Some(topic) {
+            self.store.unset_current_topic()?;
+            self.current_topic = None;
+        }
+
+        Ok(notes)
+    }
+
+    /**
+     * An archived topic is hidden from the topics list but its notes are
+     * still reachable.
+     */
+    pub fn archive_topic(&mut self, topic: &str, archived: bool) -> Result<()> {
+        if !self.store.topic_exists(topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic.to_string()));
+        }
+        self.store.set_topic_archived(topic, archived)
+    }
+
+    /**
+     * The current path of the topic, none until the first note of the topic
+     * creates its main path.
+     */
     pub fn get_current_path(&self, topic: &str) -> Result<Option<String>> {
         if self.store.topic_exists(topic) {
-            self.store.get_current_path(topic)
+            Ok(self.store.get_current_path(topic)?.filter(|path| self.store.path_exists(topic, path)))
         } else {
-            Err(From::from(ZtlnError::TopicDoesNotExist(topic.to_string())))
+            Err(ZtlnError::TopicDoesNotExist(topic.to_string()))
         }
     }
 
     pub fn set_current_path(&mut self, topic: Option<&str>, path: &str) -> Result<()> {
         let topic = self.unwrap_or_default_topic(topic)?;
         if self.store.path_exists(&topic, path) {
-            self.store.set_current_path(&topic, path)
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+            self.store.set_current_path(&topic, path)?;
                 Ok(())
         } else {
-            Err(From::from(ZtlnError::PathDoesNotExist(topic, path.to_string())))
+            Err(ZtlnError::PathDoesNotExist(topic, path.to_string()))
         }
     }
 
     pub fn create_path(&mut self, new_path: &str, location: Option<&str>) -> Result<()> {
+        check_name(new_path)?;
         let location = location.unwrap_or("HEAD").to_string();
         let metadata = self.solve_location(&location)?
-            .ok_or_else(|| ZtlnError::Default("location does not exist".to_string()))?;
-        self.store.write_path(&metadata.topic, new_path, metadata.note_id)
-            .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+            .ok_or_else(|| ZtlnError::LocationError(location.clone()))?;
+        self.store.write_path(&metadata.topic, new_path, metadata.note_id)?;
         Ok(())
     }
 
     pub fn remove_path(&mut self, path: &str, topic: Option<&str>) -> Result<NoteMetaData> {
         let metadata = self.get_metadata(path, topic)?;
-        self.store.remove_path(&metadata.topic, path)
-            .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+        self.store.remove_path(&metadata.topic, path)?;
         Ok(metadata)
     }
 
@@ -105,29 +292,179 @@ impl<'a> Organization<'a> {
 
             Ok((old_metadata, new_metadata))
         } else {
-            Err(From::from(ZtlnError::LocationError(location.to_string())))
+            Err(ZtlnError::LocationError(location.to_string()))
+        }
+
+    }
+
+    /**
+     * Rename a path, the topic's default path and the meta data of the notes
+     * created in this path are updated. Return the number of notes updated.
+     */
+    pub fn rename_path(&mut self, path: &str, new_path: &str, topic: Option<&str>) -> Result<usize> {
+        let topic = self.unwrap_or_default_topic(topic)?;
+        if !self.store.topic_exists(&topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic));
+        }
+        if !self.store.path_exists(&topic, path) {
+            return Err(ZtlnError::PathDoesNotExist(topic, path.to_string()));
+        }
+        if self.store.path_exists(&topic, new_path) {
+            return Err(ZtlnError::PathAlreadyExists(topic, new_path.to_string()));
+        }
+        check_name(new_path)?;
+        self.store.rename_path(&topic, path, new_path)?;
+        if self.store.get_current_path(&topic)?.as_deref() == Some(path) {
+            self.store.set_current_path(&topic, new_path)?;
+        }
+        let mut count = 0;
+        for mut metadata in self.get_topic_notes(&topic)? {
+            if metadata.path == path {
+                metadata.path = new_path.to_string();
+                self.store.write_note_metadata(&metadata)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /**
+     * Create a merge note in the target path (the current path by default)
+     * whose parents are the heads of the target and source paths. The target
+     * path is advanced to this note.
+     */
+    pub fn merge_path(&mut self, source: &str, target: Option<&str>, topic: Option<&str>, filename: &str) -> Result<NoteMetaData> {
+        let topic = self.unwrap_or_default_topic(topic)?;
+        if !self.store.topic_exists(&topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic));
+        }
+        let target = match target {
+            Some(path) => path.to_string(),
+            None => self.get_current_path(&topic)?
+                .ok_or_else(|| ZtlnError::Default(format!("No default path in topic '{}'.", topic)))?,
+        };
+        for path in [source, &target] {
+            if !self.store.path_exists(&topic, path) {
+                return Err(ZtlnError::PathDoesNotExist(topic, path.to_string()));
+            }
+        }
+        if source == target {
+            return Err(ZtlnError::Default(format!("Cannot merge path '{}' into itself.", source)));
+        }
+        let source_head = self.store.get_path(&topic, source)?;
+        let target_head = self.store.get_path(&topic, &target)?;
+        if self.is_ancestor(source_head, target_head)? {
+            return Err(ZtlnError::Conflict(format!("Path '{}' is already merged into '{}'.", source, target)));
+        }
+        if !std::path::Path::new(&filename).exists() || std::fs::metadata(filename)?.len() == 0 {
+            return Err(ZtlnError::Default("Note is empty, aborting operation.".to_string()));
+        }
+        let links = self.read_note_links(filename, Some((&topic, &target)))?;
+        let mut metadata = self.store.add_note(&topic, &target, filename)?;
+        metadata.parents.push(source_head);
+        self.store.write_note_metadata(&metadata)?;
+        self.apply_note_links(&mut metadata, &links)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * Return the history of a path (the current path by default), most recent
+     * note first, following the first parent of merge notes.
+     */
+    pub fn get_path_log(&mut self, path: Option<&str>, topic: Option<&str>) -> Result<Vec<NoteMetaData>> {
+        let metadata = self.get_metadata(path.unwrap_or("HEAD"), topic)?;
+
+        self.get_first_parent_history(metadata.note_id)
+    }
+
+    /**
+     * Compare two locations: find their merge base, the first note of the
+     * right side history reachable from the left side, and the notes
+     * reachable from one side only.
+     */
+    pub fn diff_paths(&mut self, left: &str, right: &str) -> Result<PathDiff> {
+        let left = self.solve_location(left)?
+            .ok_or_else(|| ZtlnError::LocationError(left.to_string()))?;
+        let right = self.solve_location(right)?
+            .ok_or_else(|| ZtlnError::LocationError(right.to_string()))?;
+        let mut base = None;
+        for meta in self.get_ancestry(right.note_id)? {
+            if self.is_ancestor(meta.note_id, left.note_id)? {
+                base = Some(meta);
+                break;
+            }
+        }
+
+        Ok(PathDiff {
+            base,
+            left: self.get_range(right.note_id, left.note_id)?,
+            right: self.get_range(left.note_id, right.note_id)?,
+        })
+    }
+
+    /**
+     * Unified diff between the concatenated contents of the notes of each
+     * side of a path comparison.
+     */
+    pub fn diff_content(&self, diff: &PathDiff, left_name: &str, right_name: &str) -> Result<String> {
+        let mut texts = Vec::new();
+        for notes in [&diff.left, &diff.right] {
+            let mut text = String::new();
+            for meta in notes {
+                let content = self.store.get_note_content(meta.note_id)?;
+                text.push_str(&content);
+                if !content.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+            texts.push(text);
         }
 
+        Ok(unified_diff(&texts[0], &texts[1], left_name, right_name))
     }
 
     pub fn get_paths_list(&mut self, topic: Option<&str>) -> Result<(String, Vec<String>)> {
         let topic = self.unwrap_or_default_topic(topic)?;
-        let paths = self.store.get_paths(&topic)
-                .unwrap_or_else(|e| self.manage_store_error::<_>(e));
+        let paths = self.store.get_paths(&topic)?;
         Ok((topic, paths))
     }
 
     pub fn add_note(&mut self, filename: &str, topic: Option<&str>, path: Option<&str>) -> Result<NoteMetaData> {
-        if !std::path::Path::new(&filename).exists() || std::fs::metadata(&filename)?.len() == 0 {
-            return Err(From::from(ZtlnError::Default("Note is empty, aborting operation.".to_string())));
-        }
+        self.add_note_with_id(Uuid::new_v4(), filename, topic, path)
+    }
 
-        if let Some(f)= topic {
-            self.set_current_topic(f)?;
-        } else if self.get_current_topic().is_none() {
-            return Err(From::from(ZtlnError::Default("No default topic".to_string())));
+    /**
+     * Add a note which UUID is already known, see `render_template`.
+     */
+    pub fn add_note_with_id(&mut self, note_id: Uuid, filename: &str, topic: Option<&str>, path: Option<&str>) -> Result<NoteMetaData> {
+        if !std::path::Path::new(&filename).exists() || std::fs::metadata(filename)?.len() == 0 {
+            return Err(ZtlnError::Default("Note is empty, aborting operation.".to_string()));
         }
-        let topic = self.get_current_topic().unwrap();
+
+        let topic = match topic {
+            Some(topic) if !self.store.topic_exists(topic) => return Err(ZtlnError::TopicDoesNotExist(topic.to_string())),
+            Some(topic) => topic.to_string(),
+            None => self.get_current_topic()?.ok_or_else(|| ZtlnError::Default("No default topic".to_string()))?,
+        };
+        // The note is read before anything is changed, its relative locations
+        // are solved from the note it will follow.
+        let parent_path = match path {
+            Some(path) if self.store.path_exists(&topic, path) => Some(path.to_string()),
+            Some(path) => {
+                check_name(path)?;
+                self.get_current_path(&topic)?
+            },
+            None => self.get_current_path(&topic)?,
+        };
+        let head_path = match (parent_path, path) {
+            (Some(parent_path), _) => parent_path,
+            (None, Some(path)) => path.to_string(),
+            (None, None) => self.get_head_name(&topic)?,
+        };
+        let links = self.read_note_links(filename, Some((&topic, &head_path)))?;
+        self.set_current_topic(&topic)?;
 
         // Path management is a bit complex since this may be the first note to be created in a path.
         // In this case, there is no existing path hence one must be created and set as default.
@@ -139,130 +476,828 @@ impl<'a> Organization<'a> {
             // 1.2 if not, if a default path exist, create a new path branching from it
             } else if let Some(curr) = self.get_current_path(&topic)? {
                 let uuid = self.store.get_path(&topic, &curr)?;
-                self.store.write_path(&topic, &new_path, uuid)?;
+                self.store.write_path(&topic, new_path, uuid)?;
                 self.set_current_path(Some(&topic), new_path)?;
             // 1.3 otherwise create a new branch from scratch
             } else {
-                self.store.set_current_path(&topic, new_path)
-                    .unwrap_or_else(|e| self.manage_store_error(e));
+                self.store.set_current_path(&topic, new_path)?;
             }
-        // 2 no path provided, if no default path exist, create abitrary "main"
+        // 2 no path provided, if no default path exist, create the default one
         } else if self.get_current_path(&topic)?.is_none() {
-            self.store.set_current_path(&topic, "main")
-                .unwrap_or_else(|e| self.manage_store_error(e));
+            self.store.set_current_path(&topic, &self.get_head_name(&topic)?)?;
         }
-        let path = self.get_current_path(&topic)?.unwrap();
-        let meta = self.store.add_note(&topic, &path, filename)?;
+        let path = self.get_head_name(&topic)?;
+        let mut meta = self.store.add_note_with_id(note_id, &topic, &path, filename)?;
+        self.apply_note_links(&mut meta, &links)?;
         
-        Ok(NoteMetaData { note_id: meta.note_id, parent_id: meta.parent_id, topic, path, references: Vec::new() })
+        Ok(meta)
+    }
+
+    /**
+     * Add a fleeting note to the inbox, outside any topic and path, until it
+     * is promoted.
+     */
+    pub fn add_fleeting_note(&mut self, filename: &str) -> Result<NoteMetaData> {
+        if !std::path::Path::new(&filename).exists() || std::fs::metadata(filename)?.len() == 0 {
+            return Err(ZtlnError::Default("Note is empty, aborting operation.".to_string()));
+        }
+        let links = self.read_note_links(filename, None)?;
+        let mut metadata = NoteMetaData {
+            note_id: Uuid::new_v4(),
+            parents: Vec::new(),
+            references: Vec::new(),
+            topic: String::new(),
+            path: String::new(),
+            created_at: Some(Utc::now().trunc_subsecs(0)),
+            origin: None,
+            title: None,
+            kind: NoteKind::Fleeting,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
+        };
+        self.store.write_note_metadata(&metadata)?;
+        self.store.update_note_content(filename, metadata.note_id)?;
+        self.apply_note_links(&mut metadata, &links)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * The fleeting notes which are not promoted yet, oldest first.
+     */
+    pub fn get_inbox(&self) -> Result<Vec<NoteMetaData>> {
+        let mut inbox = Vec::new();
+        for uuid in self.store.get_notes()? {
+            match self.store.get_note_metadata(uuid)? {
+                Some(metadata) if metadata.kind == NoteKind::Fleeting && !metadata.archived => inbox.push(metadata),
+                _ => continue,
+            }
+        }
+        inbox.sort_by_key(|metadata| metadata.created_at);
+
+        Ok(inbox)
+    }
+
+    /**
+     * Copy a fleeting note as a permanent note at the head of a topic/path
+     * (the current one by default) and archive the fleeting note, which
+     * becomes the origin of the new one.
+     */
+    pub fn promote_note(&mut self, location: &str, target: Option<&str>) -> Result<NoteMetaData> {
+        let mut source = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        if source.kind != NoteKind::Fleeting {
+            return Err(ZtlnError::Conflict(format!("Note '{}' is not a fleeting note.", location)));
+        }
+        if source.archived {
+            return Err(ZtlnError::Conflict(format!("Note '{}' is already promoted.", location)));
+        }
+        let (topic, path) = self.solve_target(target.unwrap_or("HEAD"))?;
+        let parent = self.get_target_head(&topic, &path)?;
+        let metadata = self.replay_note(&source, &topic, &path, parent)?;
+        source.archived = true;
+        self.store.write_note_metadata(&source)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * Mark a note of a path as a literature or a permanent note, fleeting
+     * notes are promoted instead.
+     */
+    pub fn set_note_kind(&mut self, metadata: &mut NoteMetaData, kind: NoteKind) -> Result<()> {
+        if kind == NoteKind::Fleeting {
+            return Err(ZtlnError::Default("Fleeting notes are added to the inbox and promoted from it.".to_string()));
+        }
+        if metadata.kind == NoteKind::Fleeting {
+            return Err(ZtlnError::Conflict("Fleeting notes are promoted from the inbox.".to_string()));
+        }
+        metadata.kind = kind;
+        self.store.write_note_metadata(metadata)
+    }
+
+    /**
+     * Add the entries of a BibTeX file to the sources of the organization,
+     * the ones with a known citekey are replaced. Return the imported
+     * sources.
+     */
+    pub fn import_sources(&mut self, filename: &str) -> Result<Vec<Source>> {
+        let content = std::fs::read_to_string(filename).context("read", filename)?;
+        let imported = Source::parse_bibtex(&content)?;
+        let mut sources = self.store.get_sources()?;
+        sources.retain(|source| !imported.iter().any(|new| new.citekey == source.citekey));
+        sources.extend(imported.iter().cloned());
+        sources.sort_by(|a, b| a.citekey.cmp(&b.citekey));
+        self.store.write_sources(&sources)?;
+
+        Ok(imported)
+    }
+
+    pub fn get_sources(&self) -> Result<Vec<Source>> {
+        self.store.get_sources()
+    }
+
+    pub fn get_source(&self, citekey: &str) -> Result<Source> {
+        self.store.get_sources()?
+            .into_iter()
+            .find(|source| source.citekey == citekey)
+            .ok_or_else(|| ZtlnError::SourceDoesNotExist(citekey.to_string()))
+    }
+
+    /**
+     * Record the source (and the page) the note at the given location comes
+     * from. The page is kept on one line, like titles.
+     */
+    pub fn cite_source(&mut self, location: &str, citekey: &str, page: Option<&str>) -> Result<NoteMetaData> {
+        let mut metadata = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        self.get_source(citekey)?;
+        let page = page.map(|page| page.split_whitespace().collect::<Vec<&str>>().join(" "))
+            .filter(|page| !page.is_empty());
+        if page.as_deref().is_some_and(|page| page.contains(char::is_control)) {
+            return Err(ZtlnError::Default("The page must not contain control characters.".to_string()));
+        }
+        metadata.source = Some(citekey.to_string());
+        metadata.page = page;
+        self.store.write_note_metadata(&metadata)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * The notes citing a source, oldest first.
+     */
+    pub fn get_citing_notes(&self, citekey: &str) -> Result<Vec<NoteMetaData>> {
+        self.get_source(citekey)?;
+        let mut notes = Vec::new();
+        for uuid in self.store.get_notes()? {
+            match self.store.get_note_metadata(uuid)? {
+                Some(metadata) if metadata.source.as_deref() == Some(citekey) => notes.push(metadata),
+                _ => continue,
+            }
+        }
+        notes.sort_by_key(|metadata| metadata.created_at);
+
+        Ok(notes)
+    }
+
+    /**
+     * The citation of the source of a note, its citekey when the source is
+     * not known anymore.
+     */
+    pub fn get_citation(&self, metadata: &NoteMetaData) -> Result<Option<String>> {
+        let citekey = match metadata.source.as_ref() {
+            Some(citekey) => citekey,
+            None => return Ok(None),
+        };
+        Ok(Some(match self.get_source(citekey) {
+            Ok(source) => source.citation(metadata.page.as_deref()),
+            Err(_) => citekey.to_string(),
+        }))
+    }
+
+    /**
+     * Copy a file in the organization and attach it to the note at the given
+     * location. A file with the same name replaces the attached one. Return
+     * the note and the name of the attachment.
+     */
+    pub fn attach_file(&mut self, location: &str, filename: &str) -> Result<(NoteMetaData, String)> {
+        let mut metadata = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        let path = std::path::Path::new(filename);
+        if !path.is_file() {
+            return Err(ZtlnError::Default(format!("'{}' is not a file.", filename)));
+        }
+        let name = path.file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| !name.contains(char::is_control))
+            .ok_or_else(|| ZtlnError::Default(format!("'{}' has no valid file name.", filename.escape_debug())))?
+            .to_string();
+        self.store.write_attachment(metadata.note_id, &name, path)?;
+        if !metadata.attachments.contains(&name) {
+            metadata.attachments.push(name.clone());
+            self.store.write_note_metadata(&metadata)?;
+        }
+
+        Ok((metadata, name))
+    }
+
+    /**
+     * Compare the attachments listed in the meta data of the notes with the
+     * files of the organization.
+     */
+    pub fn check_attachments(&self) -> Result<Vec<AttachmentProblem>> {
+        let mut problems = Vec::new();
+        for note_id in self.store.get_notes()? {
+            if let Some(metadata) = self.store.get_note_metadata(note_id)? {
+                for (name, path) in metadata.attachments.iter().zip(self.get_attachment_paths(&metadata)) {
+                    if !path.is_file() {
+                        problems.push(AttachmentProblem::Missing { note_id, name: name.to_string() });
+                    }
+                }
+            }
+        }
+        for note_id in self.store.get_attached_notes()? {
+            match self.store.get_note_metadata(note_id)? {
+                Some(metadata) => problems.extend(self.store.get_attachment_names(note_id)?.into_iter()
+                    .filter(|name| !metadata.attachments.contains(name))
+                    .map(|name| AttachmentProblem::Unlisted { note_id, name })),
+                None => problems.push(AttachmentProblem::Orphaned { note_id }),
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /**
+     * Remove the attachments of deleted notes and the files no note lists,
+     * return what was removed, or only what would be with `dry_run`. Missing
+     * attachments are left to the user.
+     */
+    pub fn prune_attachments(&mut self, dry_run: bool) -> Result<Vec<AttachmentProblem>> {
+        let mut removed = Vec::new();
+        for problem in self.check_attachments()? {
+            match &problem {
+                AttachmentProblem::Missing { .. } => continue,
+                _ if dry_run => (),
+                AttachmentProblem::Unlisted { note_id, name } => self.store.remove_attachment(*note_id, name)?,
+                AttachmentProblem::Orphaned { note_id } => self.store.remove_attachments(*note_id)?,
+            }
+            removed.push(problem);
+        }
+
+        Ok(removed)
+    }
+
+    /**
+     * The files attached to a note in the organization.
+     */
+    pub fn get_attachment_paths(&self, metadata: &NoteMetaData) -> Vec<std::path::PathBuf> {
+        metadata.attachments.iter()
+            .map(|name| self.store.get_attachment_path(metadata.note_id, name))
+            .collect()
+    }
+
+    /**
+     * Content of a new note made from a template: the file given for this name
+     * in the configuration or `templates/NAME.md` in the organization. Names
+     * which could lead out of the `templates` directory are refused. The
+     * placeholders `{{date}}`, `{{topic}}`, `{{path}}`, `{{parent_title}}`
+     * and `{{uuid}}` are replaced, the returned UUID must be given to
+     * `add_note_with_id`.
+     */
+    pub fn render_template(&mut self, name: &str, topic: Option<&str>, path: Option<&str>) -> Result<(Uuid, String)> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            return Err(ZtlnError::Default(format!("Invalid template name '{}'.", name)));
+        }
+        let file = self.templates.get(name).cloned()
+            .unwrap_or_else(|| PathBuf::from(format!("templates/{}.md", name)));
+        let template = self.store.get_template(&file)?
+            .ok_or_else(|| ZtlnError::TemplateDoesNotExist(name.to_string()))?;
+        let topic = self.unwrap_or_default_topic(topic)?;
+        if !self.store.topic_exists(&topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic));
+        }
+        let path = match path {
+            Some(path) => path.to_string(),
+            None => self.get_head_name(&topic)?,
+        };
+        let parent_title = match self.get_target_head(&topic, &path)? {
+            Some(parent) => FrontMatter::title(&self.store.get_note_content(parent)?),
+            None => String::new(),
+        };
+        let note_id = Uuid::new_v4();
+        let content = template
+            .replace("{{date}}", &Utc::now().format("%Y-%m-%d").to_string())
+            .replace("{{topic}}", &topic)
+            .replace("{{path}}", &path)
+            .replace("{{parent_title}}", &parent_title)
+            .replace("{{uuid}}", &note_id.to_string());
+
+        Ok((note_id, content))
+    }
+
+    /**
+     * Move a note to the head of another topic/path. Its children are linked
+     * to its parents and the paths pointing to it are reset to its first
+     * parent, or removed if it has none. References and tags are kept since
+     * they point to the note's UUID.
+     */
+    pub fn move_note(&mut self, location: &str, target: &str) -> Result<NoteMetaData> {
+        let mut metadata = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        if metadata.kind == NoteKind::Fleeting {
+            return Err(ZtlnError::Conflict("Fleeting notes are moved to a path by 'note promote'.".to_string()));
+        }
+        let (topic, path) = self.solve_target(target)?;
+        self.detach_note(&metadata)?;
+        metadata.parents = self.get_target_head(&topic, &path)?.into_iter().collect();
+        metadata.topic = topic;
+        metadata.path = path;
+        self.store.write_note_metadata(&metadata)?;
+        self.store.write_path(&metadata.topic, &metadata.path, metadata.note_id)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * Copy a note to the head of another topic/path. The copy has the same
+     * content, references and tags as the original note. Fleeting notes are
+     * promoted instead.
+     */
+    pub fn copy_note(&mut self, location: &str, target: &str) -> Result<NoteMetaData> {
+        let source = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        if source.kind == NoteKind::Fleeting {
+            return Err(ZtlnError::Conflict("Fleeting notes are copied to a path by 'note promote'.".to_string()));
+        }
+        let (topic, path) = self.solve_target(target)?;
+        let parent = self.get_target_head(&topic, &path)?;
+
+        self.replay_note(&source, &topic, &path, parent)
+    }
+
+    /**
+     * Copy the notes of a range `FROM..TO` (the notes reachable from TO but
+     * not from FROM, following first parents) or a single note on top of a
+     * topic/path, oldest first. Return the new notes.
+     */
+    pub fn cherry_pick(&mut self, range: &str, target: &str) -> Result<Vec<NoteMetaData>> {
+        let notes = match split_range(range) {
+            Some((from, to)) => {
+                let from = self.solve_location(from)?
+                    .ok_or_else(|| ZtlnError::LocationError(from.to_string()))?;
+                let to = self.solve_location(to)?
+                    .ok_or_else(|| ZtlnError::LocationError(to.to_string()))?;
+                self.get_range(from.note_id, to.note_id)?
+            },
+            None => vec![self.solve_location(range)?
+                .ok_or_else(|| ZtlnError::LocationError(range.to_string()))?],
+        };
+        if notes.is_empty() {
+            return Err(ZtlnError::Default(format!("No notes in range '{}'.", range)));
+        }
+        if notes.iter().any(|metadata| metadata.kind == NoteKind::Fleeting) {
+            return Err(ZtlnError::Conflict("Fleeting notes are copied to a path by 'note promote'.".to_string()));
+        }
+        let (topic, path) = self.solve_target(target)?;
+        let mut parent = self.get_target_head(&topic, &path)?;
+
+        self.with_search_index(|orga| {
+            let mut picked = Vec::new();
+            for source in &notes {
+                let metadata = orga.replay_note(source, &topic, &path, parent)?;
+                parent = Some(metadata.note_id);
+                picked.push(metadata);
+            }
+            Ok(picked)
+        })
+    }
+
+    /**
+     * Replay the notes of a path which are not reachable from the given
+     * location on top of it and move the path to the last replayed note.
+     * Nothing happens if the location is already in the path history.
+     * Return the new notes.
+     */
+    pub fn rebase_path(&mut self, path: &str, topic: Option<&str>, onto: &str) -> Result<Vec<NoteMetaData>> {
+        let (topic, path) = match topic {
+            Some(topic) => self.solve_target(&format!("{}/{}", topic, path))?,
+            None => self.solve_target(path)?,
+        };
+        if !self.store.path_exists(&topic, &path) {
+            return Err(ZtlnError::PathDoesNotExist(topic, path));
+        }
+        let head = self.store.get_note_metadata(self.store.get_path(&topic, &path)?)?
+            .ok_or_else(|| ZtlnError::LocationError(format!("{}/{}", topic, path)))?;
+        let onto = self.solve_location(onto)?
+            .ok_or_else(|| ZtlnError::LocationError(onto.to_string()))?;
+        if self.is_ancestor(onto.note_id, head.note_id)? {
+            return Ok(Vec::new());
+        }
+        let mut parent = Some(onto.note_id);
+        let range = self.get_range(onto.note_id, head.note_id)?;
+        let rebased = self.with_search_index(|orga| {
+            let mut rebased = Vec::new();
+            for source in &range {
+                let metadata = orga.replay_note(source, &topic, &path, parent)?;
+                parent = Some(metadata.note_id);
+                rebased.push(metadata);
+            }
+            Ok(rebased)
+        })?;
+        if rebased.is_empty() {
+            self.store.reset_path(&topic, &path, onto.note_id)?;
+        }
+
+        Ok(rebased)
     }
 
     pub fn add_note_reference(&mut self, from_location: &str, to_location: &str) -> Result<NoteMetaData> {
         let mut from_metadata = self.solve_location(from_location)?
-            .ok_or_else(|| ZtlnError::Default(format!("Location '{}' does not exist.", from_location)))?;
+            .ok_or_else(|| ZtlnError::LocationError(from_location.to_string()))?;
         let to_metadata = self.solve_location(to_location)?
-            .ok_or_else(|| ZtlnError::Default(format!("Location '{}' does not exist.", from_location)))?;
+            .ok_or_else(|| ZtlnError::LocationError(to_location.to_string()))?;
         from_metadata.references.push(to_metadata.note_id);
         self.store.write_note_metadata(&from_metadata)?;
         
         Ok(from_metadata)
     }
 
+    /**
+     * Replace the content of the note at the given location with the content
+     * of the file. The note keeps its UUID, its place in the paths, its
+     * references and its tags, the ones of the front-matter and the
+     * wiki-links are added.
+     */
+    pub fn edit_note(&mut self, location: &str, filename: &str) -> Result<NoteMetaData> {
+        let mut metadata = self.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        if !std::path::Path::new(&filename).exists() || std::fs::metadata(filename)?.len() == 0 {
+            return Err(ZtlnError::Default("Note is empty, aborting operation.".to_string()));
+        }
+        let links = self.read_note_links(filename, None)?;
+        self.store.update_note_content(filename, metadata.note_id)?;
+        self.apply_note_links(&mut metadata, &links)?;
+
+        Ok(metadata)
+    }
+
+    /**
+     * Read the front-matter and the wiki-links of a note file and solve their
+     * locations, links that cannot be solved are only warned about. This is
+     * done before the note is written so that a wrong location leaves the
+     * organization unchanged. Locations without a topic are solved in the
+     * given topic, `HEAD` being the given path, instead of the current ones.
+     */
+    fn read_note_links(&mut self, filename: &str, head: Option<(&str, &str)>) -> Result<NoteLinks> {
+        let content = std::fs::read_to_string(filename).context("read", filename)?;
+        let (front_matter, _) = FrontMatter::split(&content)?;
+        let front_matter = front_matter.unwrap_or_default();
+        let mut references = Vec::new();
+        for location in &front_matter.refs {
+            let metadata = self.solve_location_from(location, head)?
+                .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+            references.push(metadata.note_id);
+        }
+        let mut links = Vec::new();
+        let mut targets = Vec::new();
+        for link in wiki_links(&content) {
+            let solved = match self.solve_location_from(&link.location, head) {
+                Err(e) if e.kind() == ErrorKind::Io => return Err(e),
+                solved => solved.ok().flatten(),
+            };
+            match solved {
+                Some(metadata) => {
+                    targets.push(metadata.note_id);
+                    links.push(link);
+                },
+                None => self.warnings.push(format!("Link '[[{}]]' does not reach any note, it is kept as text.", link.location)),
+            }
+        }
+        references.extend(&targets);
+        let rewritten = match self.rewrite_links && !links.is_empty() {
+            true => Some(rewrite_links(&content, &links, &targets)),
+            false => None,
+        };
+
+        Ok(NoteLinks {
+            title: FrontMatter::title(rewritten.as_deref().unwrap_or(&content)),
+            tags: front_matter.tags,
+            references,
+            content: rewritten,
+        })
+    }
+
+    /**
+     * Set the title of a note, tag it and add references to it, unless it
+     * already has them, then store its content with rewritten links.
+     */
+    fn apply_note_links(&self, metadata: &mut NoteMetaData, links: &NoteLinks) -> Result<()> {
+        let keywords = self.store.get_note_keywords(metadata.note_id)?;
+        for tag in links.tags.iter().filter(|tag| !keywords.contains(tag)) {
+            self.store.add_keyword_index(tag, metadata)?;
+        }
+        if let Some(content) = links.content.as_ref() {
+            self.store.write_note_content(metadata.note_id, content)?;
+        }
+        let count = metadata.references.len();
+        for reference in &links.references {
+            if *reference != metadata.note_id && !metadata.references.contains(reference) {
+                metadata.references.push(*reference);
+            }
+        }
+        let title = Some(links.title.split_whitespace().collect::<Vec<&str>>().join(" ")).filter(|title| !title.is_empty());
+        if metadata.references.len() > count || metadata.title != title {
+            metadata.title = title;
+            self.store.write_note_metadata(metadata)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * The title of a note, notes added before titles were stored get the
+     * first line of their content.
+     */
+    pub fn get_title(&self, metadata: &NoteMetaData) -> Result<String> {
+        match metadata.title.as_ref() {
+            Some(title) => Ok(title.to_string()),
+            None => Ok(FrontMatter::title(&self.store.get_note_content(metadata.note_id)?)),
+        }
+    }
+
     pub fn get_note_content(&self, uuid: Uuid) -> Result<String> {
        self.store.get_note_content(uuid)
     }
 
-    pub fn solve_location(&mut self, expr: &str) -> Result<Option<NoteMetaData>> {
-        lazy_static! {
-            static ref RELATIVE_LOC: Regex = Regex::new(r"^(?:(?P<topic>\w+)/)?(?P<path>\w+)(?::-(?P<modifier>\d+))?$").unwrap();
-            static ref ABSOLUTE_LOC: Regex = Regex::new(r"^(?P<subuuid>[[:xdigit:]]{8})(?:(?:-[[:xdigit:]]{4}){3}-[[:xdigit:]]{12})?$").unwrap();
-        }
-        if ABSOLUTE_LOC.is_match(expr) {
-            let mut captures = ABSOLUTE_LOC.captures_iter(expr);
-            self.solve_absolute(&mut captures)
-        } else if RELATIVE_LOC.is_match(expr) {
-            let mut captures = RELATIVE_LOC.captures_iter(expr);
-            self.solve_relative(&mut captures)
+    /**
+     * Where a note was added, for listings: its topic and path, or
+     * `inbox:SHORT_UUID` for fleeting notes.
+     */
+    pub fn get_place(&self, metadata: &NoteMetaData) -> Result<String> {
+        if metadata.kind == NoteKind::Fleeting {
+            Ok(format!("inbox:{}", self.short_uuid(metadata.note_id)?))
         } else {
-            Err(From::from(ZtlnError::Default(format!("Invalid location expression '{}'.", expr))))
+            Ok(format!("{}/{}", metadata.topic, metadata.path))
+        }
+    }
+
+    /**
+     * Return the notes referencing the given note, oldest first.
+     */
+    pub fn get_backlinks(&self, metadata: &NoteMetaData) -> Result<Vec<NoteMetaData>> {
+        let mut backlinks = Vec::new();
+        for uuid in self.store.get_notes()? {
+            if let Some(other) = self.store.get_note_metadata(uuid)? {
+                if other.references.contains(&metadata.note_id) {
+                    backlinks.push(other);
+                }
+            }
+        }
+        backlinks.sort_by_key(|other| other.created_at);
+
+        Ok(backlinks)
+    }
+
+    /**
+     * Solve a location expression (see `Location`) to the note it designates
+     * if any.
+     */
+    pub fn solve_location(&mut self, expr: &str) -> Result<Option<NoteMetaData>> {
+        self.solve_location_from(expr, None)
+    }
+
+    /**
+     * Solve a location from the given topic and path rather than the current
+     * ones.
+     */
+    fn solve_location_from(&mut self, expr: &str, head: Option<(&str, &str)>) -> Result<Option<NoteMetaData>> {
+        let location = Location::parse(expr)?;
+        let mut some_metadata = match &location.anchor {
+            Anchor::Uuid(uuid) => self.solve_uuid(uuid)?,
+            Anchor::Path { topic, path } => self.solve_path(topic.as_deref(), path, head)?,
+            Anchor::Tag { keyword, latest } => self.solve_tag(keyword, *latest)?,
+            Anchor::Title(words) => self.solve_title(words)?,
+        };
+        for modifier in &location.modifiers {
+            some_metadata = match some_metadata {
+                Some(metadata) => self.apply_modifier(metadata, modifier)?,
+                None => break,
+            };
         }
+
+        Ok(some_metadata)
     }
 
     pub fn add_keyword(&mut self, keyword: &str, location: Option<&str>) -> Result<()> {
         let location = location.unwrap_or("HEAD");
         let meta = self.solve_location(location)?
             .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
-        self.store.add_keyword_index(keyword, &meta)
-            .unwrap_or_else(|e| self.manage_store_error(e));
+        self.store.add_keyword_index(keyword, &meta)?;
 
         Ok(())
     }
 
-    pub fn search_keyword(&self, keyword: &str) -> Vec<NoteMetaData> {
+    pub fn search_keyword(&self, keyword: &str) -> Result<Vec<NoteMetaData>> {
         self.store.get_meta_from_index(keyword)
-            .unwrap_or_else(|e| self.manage_store_error(e))
     }
 
-    pub fn list_keywords(&self) -> Vec<(String, usize)> {
+    pub fn list_keywords(&self) -> Result<Vec<(String, usize)>> {
         self.store.get_keywords()
-            .unwrap_or_else(|e| self.manage_store_error(e))
     }
 
-    fn solve_absolute(&self, captures: &mut CaptureMatches) -> Result<Option<NoteMetaData>> {
-        let cap = captures.next().unwrap();
-        let subuuid = cap.name("subuuid").unwrap().as_str().to_string();
-        let some_metadata = self.store.search_short_uuid(&subuuid)?;
+    /**
+     * Full text search in the notes contents. Results may be restricted to a
+     * topic, to the notes reachable from a path or to the notes tagged with a
+     * keyword.
+     */
+    pub fn search(&mut self, query: &str, topic: Option<&str>, path: Option<&str>, tag: Option<&str>) -> Result<Vec<SearchResult>> {
+        let path_notes = if let Some(p) = path {
+            let t = self.unwrap_or_default_topic(topic)?;
+            let uuid = self.store.get_path(&t, p)
+                .map_err(|_| ZtlnError::PathDoesNotExist(t.to_string(), p.to_string()))?;
+            Some(self.get_ancestry(uuid)?.into_iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>())
+        } else { None };
+        let tagged_notes = tag
+            .map(|k| self.search_keyword(k))
+            .transpose()?
+            .map(|list| list.into_iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>());
+        let mut results = Vec::new();
 
-        Ok(some_metadata)
+        for (uuid, score) in self.store.search_notes(query)? {
+            if path_notes.as_ref().is_some_and(|list| !list.contains(&uuid))
+                || tagged_notes.as_ref().is_some_and(|list| !list.contains(&uuid)) {
+                continue;
+            }
+            let metadata = match self.store.get_note_metadata(uuid)? {
+                Some(meta) => meta,
+                None => continue,
+            };
+            if topic.is_some_and(|t| t != metadata.topic) {
+                continue;
+            }
+            let snippet = make_snippet(&self.store.get_note_content(uuid)?, query);
+            results.push(SearchResult { metadata, score, snippet });
+        }
+
+        Ok(results)
     }
 
-    fn solve_relative(&mut self, captures: &mut CaptureMatches) -> Result<Option<NoteMetaData>> {
-        let cap = captures.next().unwrap();
+    /**
+     * Look for the notes which contents match the given regular expression.
+     * Each result comes with a location that can be used to reach the note.
+     */
+    pub fn grep(&mut self, pattern: &str, case_insensitive: bool, topic: Option<&str>) -> Result<Vec<GrepResult>> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| ZtlnError::Default(format!("Invalid regular expression: {}", e)))?;
+        let mut topic_locations: HashMap<String, HashMap<Uuid, Vec<Route>>> = HashMap::new();
+        let mut results = Vec::new();
 
-        // 1 get the TOPIC, current topic if not specified
-        let topic = if cap.name("topic").is_none() { 
-            match self.get_current_topic() {
-                Some(t) => t,
-                None => return Err(From::from(ZtlnError::Default("No default topic and no topic specified.".to_string()))),
+        for uuid in self.store.get_notes()? {
+            let metadata = match self.store.get_note_metadata(uuid)? {
+                Some(meta) => meta,
+                None => continue,
+            };
+            if topic.is_some_and(|t| t != metadata.topic) {
+                continue;
+            }
+            let lines: Vec<(usize, String)> = self.store.get_note_content(uuid)?
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| regex.is_match(line))
+                .map(|(number, line)| (number + 1, line.to_string()))
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            if !topic_locations.contains_key(&metadata.topic) {
+                let locations = self.get_topic_locations(&metadata.topic)?;
+                topic_locations.insert(metadata.topic.clone(), locations);
             }
-        } else { cap.name("topic").unwrap().as_str().to_string() };
+            let location = match topic_locations[&metadata.topic].get(&uuid) {
+                Some(candidates) => self.sort_locations(&metadata.topic, candidates)?.remove(0),
+                None => self.short_uuid(uuid)?,
+            };
+            results.push(GrepResult { metadata, location, lines });
+        }
+        results.sort_by(|a, b| a.location.cmp(&b.location));
+
+        Ok(results)
+    }
+
+    /**
+     * Return the canonical location of the given note if it is reachable from
+     * any path. The current path of the note's topic is preferred, then the
+     * path where the note is the closest to the head.
+     */
+    pub fn locate_note(&self, metadata: &NoteMetaData) -> Result<Option<String>> {
+        Ok(self.get_note_locations(metadata)?.into_iter().next())
+    }
+
+    /**
+     * Return all the locations reaching the given note, the canonical one
+     * first. Since paths only hold notes of their own topic, only the paths
+     * of the note's topic are explored.
+     */
+    pub fn get_note_locations(&self, metadata: &NoteMetaData) -> Result<Vec<String>> {
+        match self.get_topic_locations(&metadata.topic)?.get(&metadata.note_id) {
+            Some(candidates) => self.sort_locations(&metadata.topic, candidates),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn solve_uuid(&self, uuid: &str) -> Result<Option<NoteMetaData>> {
+        match Uuid::parse_str(uuid) {
+            Ok(uuid) => self.store.get_note_metadata(uuid),
+            Err(_) => self.store.search_short_uuid(uuid),
+        }
+    }
+
+    /**
+     * Return the shortest prefix designating this note without ambiguity.
+     */
+    pub fn short_uuid(&self, uuid: Uuid) -> Result<String> {
+        self.store.shortest_unique_prefix(uuid)
+    }
 
-        // 2 get the PATH, current path if HEAD
-        let path =  match cap.name("path").unwrap().as_str() {
-            "HEAD"  => self.get_current_path(&topic)?.unwrap_or("main".to_string()),
-            subpath => subpath.to_string(),
+    fn solve_path(&mut self, topic: Option<&str>, path: &str, head: Option<(&str, &str)>) -> Result<Option<NoteMetaData>> {
+        let explicit_topic = topic.is_some();
+        // 1 get the TOPIC, current (or given head) topic if not specified
+        let topic = match (topic, head) {
+            (Some(t), _) => t.to_string(),
+            (None, Some((head_topic, _))) => head_topic.to_string(),
+            (None, None) => self.get_current_topic()?
+                .ok_or_else(|| ZtlnError::Default("No default topic and no topic specified.".to_string()))?,
         };
-        
-        // 3 check if an entry exist at that location
-        let mut some_metadata = if let Ok(uuid) = self.store.get_path(&topic, &path) {
-            self.store.get_note_metadata(uuid)?
-        } else { None };
 
-        // 4 look for position modifier in history tree, 0 if not specified
-        let mut modifier = if cap.name("modifier").is_none() {
-            0_usize
-        } else { 
-            str::parse::<usize>(cap.name("modifier").unwrap().as_str())?
+        // 2 get the PATH, current (or given head) path if HEAD
+        let path = match (path, head) {
+            ("HEAD", Some((head_topic, head_path))) if head_topic == topic => head_path.to_string(),
+            ("HEAD", _)  => self.get_head_name(&topic)?,
+            (subpath, _) => subpath.to_string(),
         };
 
-        while modifier > 0 && some_metadata.is_some() {
-            some_metadata = if let Some(uuid) = some_metadata.unwrap().parent_id {
-                self.store
-                .get_note_metadata(uuid)?
-            } else { None };
-            modifier -= 1;
+        // 3 check if an entry exist at that location
+        if self.store.path_exists(&topic, &path) {
+            let uuid = self.store.get_path(&topic, &path)?;
+            self.store.get_note_metadata(uuid)
+        // 4 short UUIDs with less than 8 characters may collide with path names,
+        // they are only tried when no topic is given
+        } else if !explicit_topic && is_uuid_prefix(&path, MIN_SHORT_UUID_LENGTH) {
+            self.store.search_short_uuid(&path)
+        } else {
+            Ok(None)
         }
+    }
 
-        Ok(some_metadata)
+    fn solve_tag(&self, keyword: &str, latest: bool) -> Result<Option<NoteMetaData>> {
+        let mut list = self.store.get_meta_from_index(keyword)?;
+
+        Ok(if latest { list.pop() } else { list.into_iter().next() })
     }
+
     /**
-     * This method is called to crash the application when a IO error is
-     * trapped. This is used only to catch error from the underlying IO
-     * layer.
+     * The note which title matches the given words best, the most recent one
+     * when several match as well. Promoted fleeting notes come after their
+     * copies.
      */
-    fn manage_store_error<T>(&self, err: Box<dyn std::error::Error>) -> T {
-        eprintln!("IO ERROR: {:?}", err);
-        panic!("Crashing the application…");
+    fn solve_title(&self, words: &str) -> Result<Option<NoteMetaData>> {
+        let mut best = None;
+        for uuid in self.store.get_notes()? {
+            let metadata = match self.store.get_note_metadata(uuid)? {
+                Some(meta) => meta,
+                None => continue,
+            };
+            if let Some(score) = title_score(words, &self.get_title(&metadata)?) {
+                let key = (score, !metadata.archived, metadata.created_at);
+                if best.as_ref().is_none_or(|(best_key, _)| key > *best_key) {
+                    best = Some((key, metadata));
+                }
+            }
+        }
+
+        Ok(best.map(|(_, metadata)| metadata))
+    }
+
+    fn apply_modifier(&self, metadata: NoteMetaData, modifier: &Modifier) -> Result<Option<NoteMetaData>> {
+        match modifier {
+            Modifier::Ancestor(count) => {
+                let mut some_metadata = Some(metadata);
+                for _ in 0..*count {
+                    some_metadata = match some_metadata.and_then(|meta| meta.parent_id()) {
+                        Some(uuid) => self.store.get_note_metadata(uuid)?,
+                        None => return Ok(None),
+                    };
+                }
+                Ok(some_metadata)
+            },
+            Modifier::Parent(0) => Ok(Some(metadata)),
+            Modifier::Parent(number) => match metadata.parents.get(number - 1) {
+                Some(uuid) => self.store.get_note_metadata(*uuid),
+                None => Ok(None),
+            },
+            Modifier::Date(date) => {
+                let limit = Utc.from_utc_datetime(&date.and_hms_opt(23, 59, 59).unwrap());
+                Ok(self.get_ancestry(metadata.note_id)?
+                    .into_iter()
+                    .find(|meta| meta.created_at.is_none_or(|created_at| created_at <= limit)))
+            },
+            Modifier::Search(pattern) => {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| ZtlnError::Default(format!("Invalid regular expression: {}", e)))?;
+                for meta in self.get_ancestry(metadata.note_id)? {
+                    if regex.is_match(&self.store.get_note_content(meta.note_id)?) {
+                        return Ok(Some(meta));
+                    }
+                }
+                Ok(None)
+            },
+        }
     }
 
     /**
@@ -273,11 +1308,296 @@ impl<'a> Organization<'a> {
         let topic = if let Some(t) = topic {
             t.to_string()
         } else {
-            self.get_current_topic()
+            self.get_current_topic()?
                 .ok_or_else(|| ZtlnError::Default("No topic given.".to_string()))?
         };
 
-        Ok(topic)
+        Ok(topic)
+    }
+
+    /**
+     * Map every note reachable from a path of the given topic, following any
+     * parent, to the list of paths leading to it with the shortest route from
+     * the head: the number of the parent taken at each step.
+     */
+    fn get_topic_locations(&self, topic: &str) -> Result<HashMap<Uuid, Vec<Route>>> {
+        let mut locations: HashMap<Uuid, Vec<Route>> = HashMap::new();
+        if topic.is_empty() || !self.store.topic_exists(topic) {
+            return Ok(locations);
+        }
+        for path in self.store.get_paths(topic)? {
+            let mut visited = HashSet::new();
+            let mut queue = VecDeque::from(vec![(self.store.get_path(topic, &path)?, Vec::new())]);
+            while let Some((uuid, route)) = queue.pop_front() {
+                if !visited.insert(uuid) {
+                    continue;
+                }
+                if let Some(meta) = self.store.get_note_metadata(uuid)? {
+                    for (index, parent) in meta.parents.iter().enumerate() {
+                        let mut parent_route: Vec<usize> = route.clone();
+                        parent_route.push(index + 1);
+                        queue.push_back((*parent, parent_route));
+                    }
+                    locations.entry(uuid).or_default().push((path.clone(), route));
+                }
+            }
+        }
+
+        Ok(locations)
+    }
+
+    /**
+     * Format the given (path, route) couples as locations, the current path
+     * first then by distance to the head.
+     */
+    fn sort_locations(&self, topic: &str, candidates: &[Route]) -> Result<Vec<String>> {
+        let current = self.store.get_current_path(topic)?;
+        let mut candidates = candidates.to_vec();
+        candidates.sort_by_key(|(path, route)| (Some(path) != current.as_ref(), route.len(), path.clone()));
+
+        Ok(candidates.iter()
+            .map(|(path, route)| format_location(topic, path, route))
+            .collect())
+    }
+
+    /**
+     * Return the meta data of all the notes belonging to the given topic.
+     */
+    fn get_topic_notes(&self, topic: &str) -> Result<Vec<NoteMetaData>> {
+        let mut notes = Vec::new();
+        for uuid in self.store.get_notes()? {
+            if let Some(metadata) = self.store.get_note_metadata(uuid)? {
+                if metadata.topic == topic {
+                    notes.push(metadata);
+                }
+            }
+        }
+
+        Ok(notes)
+    }
+
+    /**
+     * The path `HEAD` stands for in the topic: its current path or, before
+     * the first note of the topic, the main path this note will create.
+     */
+    fn get_head_name(&self, topic: &str) -> Result<String> {
+        Ok(self.store.get_current_path(topic)?.unwrap_or_else(|| self.default_path.clone()))
+    }
+
+    /**
+     * Split a `[topic/]path` target, the current topic is used by default.
+     */
+    fn solve_target(&mut self, target: &str) -> Result<(String, String)> {
+        let (topic, path) = match Location::parse(target)? {
+            Location { anchor: Anchor::Path { topic, path }, modifiers } if modifiers.is_empty() => (topic, path),
+            _ => return Err(ZtlnError::Default(format!("Target '{}' must be a topic/path.", target))),
+        };
+        let topic = self.unwrap_or_default_topic(topic.as_deref())?;
+        if !self.store.topic_exists(&topic) {
+            return Err(ZtlnError::TopicDoesNotExist(topic));
+        }
+        let path = match path.as_str() {
+            "HEAD" => self.get_head_name(&topic)?,
+            _ => path,
+        };
+
+        Ok((topic, path))
+    }
+
+    /**
+     * The note a new note in the given topic/path would have as parent. As
+     * for added notes, a path which does not exist branches from the topic's
+     * current path.
+     */
+    fn get_target_head(&self, topic: &str, path: &str) -> Result<Option<Uuid>> {
+        if self.store.path_exists(topic, path) {
+            return Ok(Some(self.store.get_path(topic, path)?));
+        }
+        match self.get_current_path(topic)? {
+            Some(current) => Ok(Some(self.store.get_path(topic, &current)?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+     * Run a bulk operation with the search index loaded once, it is written
+     * back even when the operation fails part way.
+     */
+    fn with_search_index<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.store.hold_search_index()?;
+        let result = f(self);
+        self.store.release_search_index()?;
+
+        result
+    }
+
+    /**
+     * Create a copy of a note with the given parent at the head of a
+     * topic/path. The copy keeps the content, references, tags, title, kind
+     * and attachments of the source note and records it as its origin. The
+     * copy of a fleeting note is a permanent note.
+     */
+    fn replay_note(&self, source: &NoteMetaData, topic: &str, path: &str, parent: Option<Uuid>) -> Result<NoteMetaData> {
+        let metadata = NoteMetaData {
+            note_id: Uuid::new_v4(),
+            parents: parent.into_iter().collect(),
+            references: source.references.clone(),
+            topic: topic.to_string(),
+            path: path.to_string(),
+            created_at: Some(Utc::now().trunc_subsecs(0)),
+            origin: Some(source.note_id),
+            title: source.title.clone(),
+            kind: match source.kind {
+                NoteKind::Fleeting => NoteKind::Permanent,
+                kind => kind,
+            },
+            archived: false,
+            source: source.source.clone(),
+            page: source.page.clone(),
+            attachments: source.attachments.clone(),
+        };
+        self.store.write_note_metadata(&metadata)?;
+        self.store.copy_note_content(source.note_id, metadata.note_id)?;
+        self.store.copy_attachments(source.note_id, metadata.note_id)?;
+        self.store.write_path(topic, path, metadata.note_id)?;
+        for keyword in self.store.get_note_keywords(source.note_id)? {
+            self.store.add_keyword_index(&keyword, &metadata)?;
+        }
+
+        Ok(metadata)
+    }
+
+    /**
+     * Return the notes reachable from `to` following first parents which are
+     * not reachable from `from`, oldest first.
+     */
+    fn get_range(&self, from: Uuid, to: Uuid) -> Result<Vec<NoteMetaData>> {
+        let mut notes = Vec::new();
+        for meta in self.get_first_parent_history(to)? {
+            if self.is_ancestor(meta.note_id, from)? {
+                break;
+            }
+            notes.push(meta);
+        }
+        notes.reverse();
+
+        Ok(notes)
+    }
+
+    /**
+     * Remove a note from the history: its children are linked to its parents
+     * and the paths pointing to it go back to its first parent.
+     */
+    fn detach_note(&self, metadata: &NoteMetaData) -> Result<()> {
+        for uuid in self.store.get_notes()? {
+            if let Some(mut child) = self.store.get_note_metadata(uuid)? {
+                if child.parents.contains(&metadata.note_id) {
+                    let mut parents = Vec::new();
+                    for parent in child.parents.iter().flat_map(|p| if *p == metadata.note_id { metadata.parents.clone() } else { vec![*p] }) {
+                        if !parents.contains(&parent) {
+                            parents.push(parent);
+                        }
+                    }
+                    child.parents = parents;
+                    self.store.write_note_metadata(&child)?;
+                }
+            }
+        }
+        for topic in self.store.get_topics()? {
+            for path in self.store.get_paths(&topic)? {
+                if self.store.get_path(&topic, &path)? == metadata.note_id {
+                    match metadata.parent_id() {
+                        Some(parent) => self.store.reset_path(&topic, &path, parent)?,
+                        None => self.store.remove_path(&topic, &path)?,
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Test if a note is reachable from another one following any parent.
+     */
+    fn is_ancestor(&self, ancestor: Uuid, uuid: Uuid) -> Result<bool> {
+        let mut stack = vec![uuid];
+        let mut visited = HashSet::new();
+        while let Some(uuid) = stack.pop() {
+            if uuid == ancestor {
+                return Ok(true);
+            }
+            if !visited.insert(uuid) {
+                continue;
+            }
+            if let Some(meta) = self.store.get_note_metadata(uuid)? {
+                stack.extend(meta.parents);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /**
+     * Return the metadata of the given note followed by its ancestors,
+     * following the first parent of merge notes.
+     */
+    fn get_first_parent_history(&self, uuid: Uuid) -> Result<Vec<NoteMetaData>> {
+        let mut history = Vec::new();
+        let mut some_uuid = Some(uuid);
+        while let Some(uuid) = some_uuid {
+            match self.store.get_note_metadata(uuid)? {
+                Some(meta) => {
+                    some_uuid = meta.parent_id();
+                    history.push(meta);
+                },
+                None => some_uuid = None,
+            }
+        }
+
+        Ok(history)
+    }
+
+    /**
+     * Return the metadata of the given note followed by all its ancestors,
+     * following every parent of merge notes. A note always comes before its
+     * parents, first parents being walked first.
+     */
+    fn get_ancestry(&self, uuid: Uuid) -> Result<Vec<NoteMetaData>> {
+        // count the children of each reachable note
+        let mut notes = HashMap::new();
+        let mut children: HashMap<Uuid, usize> = HashMap::new();
+        let mut stack = vec![uuid];
+        while let Some(uuid) = stack.pop() {
+            if notes.contains_key(&uuid) {
+                continue;
+            }
+            if let Some(meta) = self.store.get_note_metadata(uuid)? {
+                for parent in &meta.parents {
+                    *children.entry(*parent).or_default() += 1;
+                    stack.push(*parent);
+                }
+                notes.insert(uuid, meta);
+            }
+        }
+
+        // a note is listed once all its children are
+        let mut ancestry = Vec::new();
+        let mut stack = vec![uuid];
+        while let Some(uuid) = stack.pop() {
+            if let Some(meta) = notes.remove(&uuid) {
+                for parent in meta.parents.iter().rev() {
+                    let count = children.get_mut(parent).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        stack.push(*parent);
+                    }
+                }
+                ancestry.push(meta);
+            }
+        }
+
+        Ok(ancestry)
     }
 
     /**
@@ -296,9 +1616,58 @@ impl<'a> Organization<'a> {
     }
 }
 
+/**
+ * Split a `FROM..TO` range on the first `..` leaving a valid location on its
+ * left, so `..` may appear in a `:/regex/` modifier.
+ */
+fn split_range(range: &str) -> Option<(&str, &str)> {
+    range.match_indices("..")
+        .map(|(index, _)| (&range[..index], &range[index + 2..]))
+        .find(|(from, _)| Location::parse(from).is_ok())
+}
+
+/**
+ * Check a topic or path name is usable in a location expression.
+ */
+fn check_name(name: &str) -> Result<()> {
+    Location::parse(name)
+        .ok()
+        .filter(|location| matches!(&location.anchor, Anchor::Path { topic: None, .. }) && location.modifiers.is_empty())
+        .filter(|_| name != "HEAD")
+        .ok_or_else(|| ZtlnError::Default(format!("Invalid name '{}'.", name)))?;
+
+    Ok(())
+}
+
+/**
+ * Format an absolute location: `topic/path` followed by the `:-N` and `^N`
+ * moves of the route.
+ */
+fn format_location(topic: &str, path: &str, route: &[usize]) -> String {
+    let mut location = format!("{}/{}", topic, path);
+    let mut ancestors = 0;
+    for parent in route {
+        if *parent == 1 {
+            ancestors += 1;
+            continue;
+        }
+        if ancestors > 0 {
+            location.push_str(&format!(":-{}", ancestors));
+            ancestors = 0;
+        }
+        location.push_str(&format!("^{}", parent));
+    }
+    if ancestors > 0 {
+        location.push_str(&format!(":-{}", ancestors));
+    }
+
+    location
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorKind;
 
     #[test]
     fn create_organization() {
@@ -306,7 +1675,7 @@ mod tests {
         let store = Store::init(base_dir);
         assert!(store.is_ok());
         let mut orga = Organization::new(store.unwrap());
-        assert_eq!(None, orga.get_current_topic());
+        assert_eq!(None, orga.get_current_topic().unwrap());
         
         let store = Store::init(base_dir);
         assert!(store.is_err());
@@ -319,15 +1688,15 @@ mod tests {
         let base_dir = "tmp/ztln_orga2";
         let mut orga = Organization::new( Store::init(base_dir).unwrap());
 
-        assert_eq!("NONE", orga.get_current_topic().unwrap_or_else(|| "NONE".to_string()));
+        assert_eq!("NONE", orga.get_current_topic().unwrap().unwrap_or_else(|| "NONE".to_string()));
         orga.create_topic("topic1").unwrap();
-        assert_eq!("topic1", orga.get_current_topic().unwrap_or_else(|| "NONE".to_string()));
+        assert_eq!("topic1", orga.get_current_topic().unwrap().unwrap_or_else(|| "NONE".to_string()));
         orga.set_current_topic("topic1").unwrap();
-        assert_eq!("topic1", orga.get_current_topic().unwrap_or_else(|| "NONE".to_string()));
+        assert_eq!("topic1", orga.get_current_topic().unwrap().unwrap_or_else(|| "NONE".to_string()));
         orga.create_topic("topic2").unwrap();
-        assert_eq!("topic1", orga.get_current_topic().unwrap_or_else(|| "NONE".to_string()));
+        assert_eq!("topic1", orga.get_current_topic().unwrap().unwrap_or_else(|| "NONE".to_string()));
         orga.set_current_topic("topic2").unwrap();
-        assert_eq!("topic2", orga.get_current_topic().unwrap_or_else(|| "NONE".to_string()));
+        assert_eq!("topic2", orga.get_current_topic().unwrap().unwrap_or_else(|| "NONE".to_string()));
         assert!(orga.set_current_topic("topic3").is_err());
 
         std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
@@ -345,15 +1714,15 @@ mod tests {
         assert!(res1.is_err(), "empty file returns an error");
         std::fs::write(filename, "This is test 3 content").unwrap();
         let res1 = orga.add_note(filename, None, None).unwrap();
-        assert!(res1.parent_id.is_none());
+        assert!(res1.parent_id().is_none());
         assert_eq!(topic, res1.topic);
         assert_eq!("main", res1.path);
         assert_eq!("main", orga.get_current_path(topic).unwrap().unwrap());
         let res2 = orga.add_note(filename, None, None).unwrap();
-        assert_eq!(Some(res1.note_id), res2.parent_id);
+        assert_eq!(Some(res1.note_id), res2.parent_id());
         let res3 = orga.add_note(filename, None, Some("path1")).unwrap();
         assert_eq!("path1", orga.get_current_path(topic).unwrap().unwrap());
-        assert_eq!(Some(res2.note_id), res3.parent_id);
+        assert_eq!(Some(res2.note_id), res3.parent_id());
         assert!(orga.store.path_exists(topic, "path1"));
         let res4 = orga.add_note(filename, Some("wrong"), None);
         assert!(res4.is_err());
@@ -361,7 +1730,7 @@ mod tests {
         orga.create_topic(topic).unwrap();
         orga.set_current_topic(topic).unwrap();
         let res5 = orga.add_note(filename, None, None).unwrap();
-        assert!(res5.parent_id.is_none());
+        assert!(res5.parent_id().is_none());
         assert_eq!(topic, res5.topic);
         assert_eq!("main", res5.path);
         let topic = "topic3";
@@ -369,6 +1738,9 @@ mod tests {
         orga.create_topic(topic).unwrap();
         let res6 = orga.add_note(filename, Some(topic), Some(path));
         assert!(res6.is_ok());
+        assert_eq!(ErrorKind::Invalid, orga.add_note(filename, Some(topic), Some("HEAD")).unwrap_err().kind());
+        assert_eq!(ErrorKind::Invalid, orga.add_note(filename, Some(topic), Some("bad.name")).unwrap_err().kind());
+        assert_eq!(path, orga.get_current_path(topic).unwrap().unwrap());
 
         std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
     }
@@ -390,9 +1762,16 @@ mod tests {
         assert!(res1.is_ok());
         assert_eq!(2, orga.get_paths_list(Some(topic)).unwrap().1.len());
         let report2 = orga.add_note(filename, Some(topic), Some("path2")).unwrap();
-        assert_eq!(report1.note_id, report2.parent_id.unwrap());
+        assert_eq!(report1.note_id, report2.parent_id().unwrap());
         let res1 = orga.create_path("whatever", Some("wrong/HEAD"));
         assert!(res1.is_err());
+        assert_eq!(ErrorKind::NotFound, orga.create_path("whatever", Some("main~3")).unwrap_err().kind());
+        for name in &["HEAD", "bad.name", "a:-1", "topic1/x"] {
+            assert_eq!(ErrorKind::Invalid, orga.create_path(name, None).unwrap_err().kind(), "{}", name);
+        }
+        assert_eq!(ErrorKind::Invalid, orga.create_topic("x/y").unwrap_err().kind());
+        assert_eq!(ErrorKind::Invalid, orga.create_topic("HEAD").unwrap_err().kind());
+        assert!(!orga.store.topic_exists("x/y"));
 
         std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
     }
@@ -414,6 +1793,7 @@ mod tests {
             "topic1/whatever:-1",
             "whatever/main:-0",
             "44a0f45f",
+            "44a0f45f-22b6",
             "44a0f45f-22b6-4675-a277-e196d8881ca8"
         ];
 
@@ -430,6 +1810,8 @@ mod tests {
             println!("Testing location '{}' is good…", expr);
             assert!(orga.solve_location(expr).is_ok());
         }
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
     }
 
     #[test]
@@ -438,7 +1820,7 @@ mod tests {
             "",
             "tata:toto",
             "tata:+1",
-            "44a0f45f-22b6",
+            "main:-",
             "tata/toto/tete",
         ];
 
@@ -511,6 +1893,10 @@ mod tests {
         assert!(result.is_ok());
         let meta = result.unwrap();
         assert_eq!(1, meta.references.len());
+        let error = orga.add_note_reference("path1", "main~5").unwrap_err();
+        assert_eq!(ErrorKind::NotFound, error.kind());
+        assert!(error.to_string().contains("main~5"), "{}", error);
+        assert_eq!(ErrorKind::NotFound, orga.add_note_reference("wrong", "main").unwrap_err().kind());
 
         std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
     }
@@ -553,4 +1939,877 @@ mod tests {
 
         std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn search() {
+        let base_dir = "tmp/ztln_orga11";
+        let filename = "tmp/test11";
+        let topic = "topic1";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic(topic).unwrap();
+        std::fs::write(filename, "Ownership is checked at compile time").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        orga.create_path("path1", None).unwrap();
+        std::fs::write(filename, "Borrowing does not take ownership").unwrap();
+        let meta2 = orga.add_note(filename, None, Some("path1")).unwrap();
+        orga.add_keyword("rust", Some("main")).unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "Ownership of a house").unwrap();
+        orga.add_note(filename, Some("topic2"), None).unwrap();
+
+        let results = orga.search("ownership", None, None, None).unwrap();
+        assert_eq!(3, results.len());
+        assert!(results[0].score >= results[1].score, "results are ranked");
+        let results = orga.search("ownership", Some(topic), None, None).unwrap();
+        assert_eq!(2, results.len());
+        let results = orga.search("ownership", Some(topic), Some("main"), None).unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(meta1.note_id, results[0].metadata.note_id);
+        assert_eq!("Ownership is checked at compile time", results[0].snippet);
+        let results = orga.search("ownership", Some(topic), Some("path1"), None).unwrap();
+        assert_eq!(2, results.len());
+        let results = orga.search("borrowing", None, None, Some("rust")).unwrap();
+        assert!(results.is_empty());
+        let results = orga.search("borrowing", None, None, None).unwrap();
+        assert_eq!(meta2.note_id, results[0].metadata.note_id);
+        assert!(orga.search("ownership", Some(topic), Some("wrong"), None).is_err());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn grep() {
+        let base_dir = "tmp/ztln_orga12";
+        let filename = "tmp/test12";
+        let topic = "topic1";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic(topic).unwrap();
+        std::fs::write(filename, "First line\nOwnership is checked at compile time").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "nothing here").unwrap();
+        orga.add_note(filename, None, None).unwrap();
+        orga.create_path("path1", None).unwrap();
+        std::fs::write(filename, "ownership\nand OWNERSHIP").unwrap();
+        let meta3 = orga.add_note(filename, None, Some("path1")).unwrap();
+
+        let results = orga.grep("Ownership", false, None).unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(meta1.note_id, results[0].metadata.note_id);
+        assert_eq!("topic1/path1:-2", results[0].location, "current path is preferred");
+        assert_eq!(vec![(2, "Ownership is checked at compile time".to_string())], results[0].lines);
+        let results = orga.grep("ownership", true, None).unwrap();
+        assert_eq!(2, results.len());
+        assert_eq!("topic1/path1", results[0].location);
+        assert_eq!(meta3.note_id, results[0].metadata.note_id);
+        assert_eq!(2, results[0].lines.len());
+        orga.set_current_path(None, "main").unwrap();
+        assert_eq!(Some("topic1/main:-1".to_string()), orga.locate_note(&meta1).unwrap());
+        assert!(orga.grep("ownership", true, Some("topic2")).unwrap().is_empty());
+        assert!(orga.grep("(unclosed", false, None).is_err());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn note_locations() {
+        let base_dir = "tmp/ztln_orga13";
+        let filename = "tmp/test13";
+        let topic = "topic1";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic(topic).unwrap();
+        std::fs::write(filename, "This is test 13 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        orga.create_path("path1", Some("main:-1")).unwrap();
+        orga.add_note(filename, None, Some("path1")).unwrap();
+        orga.create_path("path2", None).unwrap();
+        orga.set_current_path(None, "main").unwrap();
+        assert_eq!(
+            vec!["topic1/main:-1", "topic1/path1:-1", "topic1/path2:-1"],
+            orga.get_note_locations(&meta1).unwrap()
+        );
+        assert_eq!(Some("topic1/main".to_string()), orga.locate_note(&meta2).unwrap());
+        orga.reset_path("main", None, "main:-1").unwrap();
+        assert!(orga.get_note_locations(&meta2).unwrap().is_empty());
+        assert_eq!(None, orga.locate_note(&meta2).unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn merged_note_locations() {
+        let base_dir = "tmp/ztln_orga36";
+        let filename = "tmp/test36";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "This is test 36 content").unwrap();
+        let root = orga.add_note(filename, None, None).unwrap();
+        let main1 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "Borrowing rules").unwrap();
+        let branch1 = orga.add_note(filename, None, Some("branch")).unwrap();
+        let branch2 = orga.add_note(filename, None, Some("branch")).unwrap();
+        orga.set_current_path(None, "main").unwrap();
+        let merge = orga.merge_path("branch", None, None, filename).unwrap();
+        orga.remove_path("branch", None).unwrap();
+        assert_eq!(vec!["topic1/main^2"], orga.get_note_locations(&branch2).unwrap());
+        assert_eq!(Some("topic1/main^2:-1".to_string()), orga.locate_note(&branch1).unwrap());
+        assert_eq!(Some("topic1/main:-1".to_string()), orga.locate_note(&main1).unwrap());
+        assert_eq!(Some("topic1/main:-2".to_string()), orga.locate_note(&root).unwrap());
+        assert_eq!(branch1, orga.solve_location("topic1/main^2:-1").unwrap().unwrap());
+        let ancestry: Vec<Uuid> = orga.get_ancestry(merge.note_id).unwrap().iter().map(|meta| meta.note_id).collect();
+        assert_eq!(vec![merge.note_id, branch2.note_id, branch1.note_id, main1.note_id, root.note_id], ancestry, "children come before their parents");
+        assert_eq!(3, orga.search("borrowing", None, Some("main"), None).unwrap().len());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn location_grammar() {
+        let base_dir = "tmp/ztln_orga14";
+        let filename = "tmp/test14";
+        let topic = "topic1";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic(topic).unwrap();
+        std::fs::write(filename, "First note about ownership").unwrap();
+        let mut meta1 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "Second note about borrowing").unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "Third note").unwrap();
+        let meta3 = orga.add_note(filename, None, Some("my-path")).unwrap();
+        orga.add_keyword("rust", Some("main:-1")).unwrap();
+        orga.add_keyword("rust", Some("main")).unwrap();
+        meta1.created_at = Some(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap());
+        orga.store.write_note_metadata(&meta1).unwrap();
+        let short_uuid = orga.short_uuid(meta3.note_id).unwrap();
+        let relative_uuid = format!("{}:-1", short_uuid);
+        let topic_uuid = format!("{}/{}", topic, &short_uuid[..5]);
+
+        let expectations = vec![
+            ("my-path", Some(meta3.note_id)),
+            ("my-path~", Some(meta2.note_id)),
+            ("my-path~2", Some(meta1.note_id)),
+            ("my-path^^", Some(meta1.note_id)),
+            ("my-path^2", None),
+            (short_uuid.as_str(), Some(meta3.note_id)),
+            (relative_uuid.as_str(), Some(meta2.note_id)),
+            (&short_uuid[..5], Some(meta3.note_id)),
+            (topic_uuid.as_str(), None),
+            ("#rust", Some(meta2.note_id)),
+            ("#rust:latest", Some(meta2.note_id)),
+            ("#rust:first", Some(meta1.note_id)),
+            ("#rust~1", Some(meta1.note_id)),
+            ("#unknown", None),
+            ("my-path@2024-05-01", Some(meta1.note_id)),
+            ("my-path@2024-04-30", None),
+            ("my-path:/borrow/", Some(meta2.note_id)),
+            ("my-path:/[Oo]wnership/", Some(meta1.note_id)),
+            ("my-path:/nothing/", None),
+        ];
+        for (expr, expected) in expectations {
+            let result = orga.solve_location(expr).unwrap().map(|meta| meta.note_id);
+            assert_eq!(expected, result, "solving location '{}'", expr);
+        }
+        let error = orga.solve_location("main:+1").unwrap_err().to_string();
+        assert!(error.contains("expected ':-N' or ':/regex/' after ':'"), "helpful error message: {}", error);
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn rename_topic() {
+        let base_dir = "tmp/ztln_orga15";
+        let filename = "tmp/test15";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "This is test 15 content").unwrap();
+        orga.add_note(filename, None, None).unwrap();
+        orga.add_note(filename, None, None).unwrap();
+        orga.add_note(filename, Some("topic2"), None).unwrap();
+        orga.set_current_topic("topic1").unwrap();
+        assert!(orga.rename_topic("wrong", "whatever").is_err());
+        assert!(orga.rename_topic("topic1", "topic2").is_err());
+        assert!(orga.rename_topic("topic1", "a/b").is_err());
+        assert_eq!(2, orga.rename_topic("topic1", "renamed").unwrap());
+        assert_eq!(Some("renamed".to_string()), orga.get_current_topic().unwrap());
+        assert_eq!(Some("renamed".to_string()), orga.store.get_current_topic().unwrap());
+        assert_eq!(vec!["renamed", "topic2"], orga.get_topics_list(false).unwrap());
+        let meta = orga.solve_location("renamed/main:-1").unwrap().unwrap();
+        assert_eq!("renamed", meta.topic);
+        assert_eq!("topic2", orga.solve_location("topic2/main").unwrap().unwrap().topic);
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn delete_topic() {
+        let base_dir = "tmp/ztln_orga16";
+        let filename = "tmp/test16";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "This is test 16 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        orga.add_keyword("tag", None).unwrap();
+        orga.add_note(filename, Some("topic2"), None).unwrap();
+        orga.add_note_reference("topic2/main", "topic1/main").unwrap();
+        assert!(orga.delete_topic("wrong", false).is_err());
+        assert!(orga.delete_topic("topic1", false).is_err(), "notes of topic1 are referenced");
+        let deleted = orga.delete_topic("topic1", true).unwrap();
+        assert_eq!(vec![meta1], deleted);
+        assert_eq!(vec!["topic2"], orga.get_topics_list(true).unwrap());
+        assert!(orga.solve_location("topic2/main").unwrap().unwrap().references.is_empty());
+        assert!(orga.search_keyword("tag").unwrap().is_empty());
+        assert_eq!(1, orga.store.get_notes().unwrap().len());
+        orga.delete_topic("topic2", false).unwrap();
+        assert_eq!(None, orga.get_current_topic().unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn archive_topic() {
+        let base_dir = "tmp/ztln_orga17";
+        let filename = "tmp/test17";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "This is test 17 content").unwrap();
+        let meta = orga.add_note(filename, Some("topic2"), None).unwrap();
+        assert!(orga.archive_topic("wrong", true).is_err());
+        orga.archive_topic("topic2", true).unwrap();
+        assert_eq!(vec!["topic1"], orga.get_topics_list(false).unwrap());
+        assert_eq!(vec!["topic1", "topic2"], orga.get_topics_list(true).unwrap());
+        assert!(orga.is_topic_archived("topic2"));
+        assert_eq!(meta, orga.solve_location("topic2/main").unwrap().unwrap(), "archived topics are still resolvable");
+        orga.archive_topic("topic2", false).unwrap();
+        assert_eq!(vec!["topic1", "topic2"], orga.get_topics_list(false).unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn topic_description() {
+        let base_dir = "tmp/ztln_orga18";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        assert!(orga.set_topic_description("wrong", "whatever").is_err());
+        assert!(orga.get_topic_description("wrong").is_err());
+        assert_eq!(None, orga.get_topic_description("topic1").unwrap());
+        orga.set_topic_description("topic1", "  Notes about Rust.\n").unwrap();
+        assert_eq!(Some("Notes about Rust.".to_string()), orga.get_topic_description("topic1").unwrap());
+        orga.rename_topic("topic1", "rust").unwrap();
+        assert_eq!(Some("Notes about Rust.".to_string()), orga.get_topic_description("rust").unwrap());
+        orga.set_topic_description("rust", " ").unwrap();
+        assert_eq!(None, orga.get_topic_description("rust").unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn rename_path() {
+        let base_dir = "tmp/ztln_orga19";
+        let filename = "tmp/test19";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic_with_main_path("topic1", "trunk").unwrap();
+        assert_eq!(None, orga.get_current_path("topic1").unwrap(), "the main path is created with the first note");
+        assert!(orga.create_topic_with_main_path("topic2", "HEAD").is_err());
+        assert!(!orga.store.topic_exists("topic2"));
+        std::fs::write(filename, "This is test 19 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        assert_eq!("trunk", meta1.path);
+        orga.add_note(filename, None, Some("branch")).unwrap();
+        orga.add_note(filename, None, None).unwrap();
+        orga.set_current_path(None, "trunk").unwrap();
+        assert!(orga.rename_path("wrong", "whatever", None).is_err());
+        assert!(orga.rename_path("trunk", "branch", None).is_err());
+        assert!(orga.rename_path("trunk", "a:-1", None).is_err());
+        assert_eq!(1, orga.rename_path("trunk", "main", None).unwrap());
+        assert_eq!(Some("main".to_string()), orga.get_current_path("topic1").unwrap());
+        assert_eq!(vec!["branch", "main"], orga.get_paths_list(None).unwrap().1);
+        assert_eq!("main", orga.solve_location("topic1/main").unwrap().unwrap().path);
+        assert_eq!(2, orga.rename_path("branch", "feature", Some("topic1")).unwrap());
+        let meta3 = orga.solve_location("feature").unwrap().unwrap();
+        assert_eq!("feature", meta3.path);
+        assert_eq!("feature", orga.store.get_note_metadata(meta3.parent_id().unwrap()).unwrap().unwrap().path);
+        assert_eq!(Some("main".to_string()), orga.get_current_path("topic1").unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn merge_path() {
+        let base_dir = "tmp/ztln_orga20";
+        let filename = "tmp/test20";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "This is test 20 content").unwrap();
+        let root = orga.add_note(filename, None, None).unwrap();
+        let main1 = orga.add_note(filename, None, None).unwrap();
+        let branch1 = orga.add_note(filename, None, Some("branch")).unwrap();
+        orga.set_current_path(None, "main").unwrap();
+        assert!(orga.merge_path("wrong", None, None, filename).is_err());
+        assert!(orga.merge_path("main", None, None, filename).is_err(), "cannot merge a path into itself");
+        let merge = orga.merge_path("branch", None, None, filename).unwrap();
+        assert_eq!(vec![main1.note_id, branch1.note_id], merge.parents);
+        assert_eq!("main", merge.path);
+        assert_eq!(merge, orga.solve_location("main").unwrap().unwrap());
+        assert_eq!(main1, orga.solve_location("main^").unwrap().unwrap());
+        assert_eq!(main1, orga.solve_location("main~1").unwrap().unwrap(), "first parent is followed");
+        assert_eq!(branch1, orga.solve_location("main^2").unwrap().unwrap());
+        assert_eq!(merge, orga.solve_location("main^0").unwrap().unwrap());
+        assert_eq!(None, orga.solve_location("main^3").unwrap());
+        assert_eq!(root, orga.solve_location("main^2~2").unwrap().unwrap());
+        assert!(orga.merge_path("branch", Some("main"), None, filename).is_err(), "already merged");
+        let log: Vec<Uuid> = orga.get_path_log(None, None).unwrap().iter().map(|meta| meta.note_id).collect();
+        assert_eq!(vec![merge.note_id, main1.note_id, root.note_id], log);
+        let log = orga.get_path_log(Some("branch"), Some("topic1")).unwrap();
+        assert_eq!(vec![branch1.note_id, main1.note_id, root.note_id], log.iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>());
+
+        let branch2 = orga.add_note(filename, None, Some("branch")).unwrap();
+        orga.set_current_path(None, "main").unwrap();
+        std::fs::write(filename, "---\ntitle: Synthesis\ntags: [merged]\n---\nSee [[main~1]].\n").unwrap();
+        let merge2 = orga.merge_path("branch", None, None, filename).unwrap();
+        assert_eq!(vec![merge.note_id, branch2.note_id], merge2.parents);
+        assert_eq!(vec![main1.note_id], merge2.references, "links are solved from the target head");
+        assert_eq!(Some("Synthesis".to_string()), merge2.title);
+        assert_eq!(merge2, orga.solve_location("main").unwrap().unwrap());
+        assert_eq!(vec![("merged".to_string(), 1)], orga.list_keywords().unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn move_note() {
+        let base_dir = "tmp/ztln_orga21";
+        let filename = "tmp/test21";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "This is test 21 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        let meta3 = orga.add_note(filename, None, None).unwrap();
+        let other = orga.add_note(filename, Some("topic2"), None).unwrap();
+        orga.add_keyword("tag", Some("topic1/main~1")).unwrap();
+        orga.add_note_reference("topic1/main", "topic1/main~1").unwrap();
+        assert!(orga.move_note("topic1/main~1", "wrong/main").is_err());
+        assert!(orga.move_note("topic1/main~1", "topic2/main~1").is_err());
+        let moved = orga.move_note("topic1/main~1", "topic2/main").unwrap();
+        assert_eq!(meta2.note_id, moved.note_id);
+        assert_eq!(vec![other.note_id], moved.parents);
+        assert_eq!(("topic2", "main"), (moved.topic.as_str(), moved.path.as_str()));
+        assert_eq!(moved, orga.solve_location("topic2/main").unwrap().unwrap());
+        let head = orga.solve_location("topic1/main").unwrap().unwrap();
+        assert_eq!(meta3.note_id, head.note_id);
+        assert_eq!(vec![meta1.note_id], head.parents, "children are linked to the moved note's parent");
+        assert_eq!(vec![meta2.note_id], head.references, "references are kept");
+        assert_eq!(moved, orga.solve_location("#tag").unwrap().unwrap(), "tags are kept");
+        orga.move_note("topic1/main", "topic2/other").unwrap();
+        assert_eq!(meta1.note_id, orga.solve_location("topic1/main").unwrap().unwrap().note_id, "path goes back to the parent");
+        assert_eq!(vec![moved.note_id], orga.solve_location("topic2/other").unwrap().unwrap().parents, "new path branches from the current one");
+        orga.move_note("topic1/main", "topic2/main").unwrap();
+        assert!(orga.get_paths_list(Some("topic1")).unwrap().1.is_empty(), "a path without notes is removed");
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn copy_note() {
+        let base_dir = "tmp/ztln_orga22";
+        let filename = "tmp/test22";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "This is test 22 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        orga.add_keyword("tag", None).unwrap();
+        orga.add_note_reference("topic1/main", "topic1/main~1").unwrap();
+        let copy = orga.copy_note("topic1/main", "topic2/main").unwrap();
+        assert_ne!(meta2.note_id, copy.note_id);
+        assert!(copy.parents.is_empty());
+        assert_eq!(vec![meta1.note_id], copy.references);
+        assert_eq!(copy, orga.solve_location("topic2/main").unwrap().unwrap());
+        assert_eq!(copy, orga.solve_location("#tag").unwrap().unwrap());
+        assert_eq!(2, orga.search_keyword("tag").unwrap().len());
+        assert_eq!(meta2.note_id, orga.solve_location("topic1/main").unwrap().unwrap().note_id, "the original is untouched");
+        assert_eq!(orga.get_note_content(meta2.note_id).unwrap(), orga.get_note_content(copy.note_id).unwrap());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn cherry_pick() {
+        let base_dir = "tmp/ztln_orga23";
+        let filename = "tmp/test23";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        orga.create_topic("topic2").unwrap();
+        let mut notes = Vec::new();
+        for index in 0..4 {
+            std::fs::write(filename, format!("note {}", index)).unwrap();
+            notes.push(orga.add_note(filename, Some("topic1"), None).unwrap());
+        }
+        std::fs::write(filename, "other").unwrap();
+        let other = orga.add_note(filename, Some("topic2"), None).unwrap();
+        orga.set_current_topic("topic1").unwrap();
+        assert!(orga.cherry_pick("main..main", "topic2/main").is_err(), "empty range");
+        let picked = orga.cherry_pick("main~3..main~1", "topic2/main").unwrap();
+        assert_eq!(2, picked.len());
+        assert_eq!(vec![Some(notes[1].note_id), Some(notes[2].note_id)], picked.iter().map(|m| m.origin).collect::<Vec<_>>());
+        assert_eq!(vec![other.note_id], picked[0].parents);
+        assert_eq!(vec![picked[0].note_id], picked[1].parents);
+        assert_eq!("note 2", orga.get_note_content(picked[1].note_id).unwrap());
+        assert_eq!(picked[1], orga.solve_location("topic2/main").unwrap().unwrap());
+        let picked = orga.cherry_pick("main:/n..e 0/..main:/note 3/", "topic2/other").unwrap();
+        assert_eq!(3, picked.len());
+        let picked = orga.cherry_pick("main", "topic2/main").unwrap();
+        assert_eq!(Some(notes[3].note_id), picked[0].origin);
+        assert_eq!(notes[3].note_id, orga.solve_location("topic1/main").unwrap().unwrap().note_id, "source is untouched");
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn rebase_path() {
+        let base_dir = "tmp/ztln_orga24";
+        let filename = "tmp/test24";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "root").unwrap();
+        let root = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "feature").unwrap();
+        let feature = orga.add_note(filename, None, Some("feature")).unwrap();
+        orga.set_current_path(None, "main").unwrap();
+        std::fs::write(filename, "main").unwrap();
+        let main = orga.add_note(filename, None, None).unwrap();
+        assert!(orga.rebase_path("wrong", None, "main").is_err());
+        let rebased = orga.rebase_path("feature", None, "main").unwrap();
+        assert_eq!(1, rebased.len());
+        assert_eq!(vec![main.note_id], rebased[0].parents);
+        assert_eq!(Some(feature.note_id), rebased[0].origin);
+        assert_eq!("feature", rebased[0].path);
+        assert_eq!(rebased[0], orga.solve_location("feature").unwrap().unwrap());
+        assert_eq!(root.note_id, orga.solve_location("feature~2").unwrap().unwrap().note_id);
+        assert!(orga.rebase_path("feature", Some("topic1"), "main").unwrap().is_empty(), "already up to date");
+        assert_eq!(rebased[0], orga.solve_location("feature").unwrap().unwrap());
+        orga.rebase_path("main", None, "feature").unwrap();
+        assert_eq!(rebased[0], orga.solve_location("main").unwrap().unwrap(), "fast forward");
+
+        std::fs::write(filename, "side").unwrap();
+        let side = orga.add_note(filename, None, Some("side")).unwrap();
+        std::fs::write(filename, "main 2").unwrap();
+        let main2 = orga.add_note(filename, None, Some("main")).unwrap();
+        orga.set_current_path(None, "side").unwrap();
+        let rebased = orga.rebase_path("HEAD", None, "main").unwrap();
+        assert_eq!((vec![main2.note_id], Some(side.note_id)), (rebased[0].parents.clone(), rebased[0].origin));
+        assert_eq!(rebased[0], orga.solve_location("side").unwrap().unwrap(), "HEAD is the current path");
+        assert_eq!(vec!["feature", "main", "side"], orga.get_paths_list(None).unwrap().1);
+        std::fs::write(filename, "main 3").unwrap();
+        let main3 = orga.add_note(filename, None, Some("main")).unwrap();
+        let rebased = orga.rebase_path("topic1/side", None, "main").unwrap();
+        assert_eq!(vec![main3.note_id], rebased[0].parents);
+        assert_eq!(("topic1", "side"), (rebased[0].topic.as_str(), rebased[0].path.as_str()));
+        let count = orga.store.get_notes().unwrap().len();
+        assert_eq!(ErrorKind::NotFound, orga.rebase_path("topic1/missing", None, "feature").unwrap_err().kind());
+        assert!(orga.rebase_path("side~1", None, "feature").is_err());
+        assert_eq!(count, orga.store.get_notes().unwrap().len(), "nothing is written for an invalid path");
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn diff_paths() {
+        let base_dir = "tmp/ztln_orga25";
+        let filename = "tmp/test25";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "root").unwrap();
+        let root = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "left 1\n").unwrap();
+        let left1 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "left 2").unwrap();
+        let left2 = orga.add_note(filename, None, None).unwrap();
+        orga.create_path("right", Some("main~2")).unwrap();
+        std::fs::write(filename, "right 1").unwrap();
+        let right1 = orga.add_note(filename, None, Some("right")).unwrap();
+        let ids = |notes: &Vec<NoteMetaData>| notes.iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>();
+        let diff = orga.diff_paths("main", "right").unwrap();
+        assert_eq!(Some(root.note_id), diff.base.as_ref().map(|meta| meta.note_id));
+        assert_eq!(vec![left1.note_id, left2.note_id], ids(&diff.left));
+        assert_eq!(vec![right1.note_id], ids(&diff.right));
+        assert_eq!(
+            "--- main\n+++ right\n@@ -1,2 +1 @@\n-left 1\n-left 2\n+right 1\n",
+            orga.diff_content(&diff, "main", "right").unwrap()
+        );
+        let diff = orga.diff_paths("main", "main~1").unwrap();
+        assert_eq!(Some(left1), diff.base);
+        assert_eq!(vec![left2], diff.left);
+        assert!(diff.right.is_empty());
+        assert!(orga.diff_paths("main", "wrong").is_err());
+        orga.create_topic("topic2").unwrap();
+        std::fs::write(filename, "other").unwrap();
+        let other = orga.add_note(filename, Some("topic2"), None).unwrap();
+        let diff = orga.diff_paths("topic1/right", "topic2/main").unwrap();
+        assert_eq!(None, diff.base);
+        assert_eq!(vec![root.note_id, right1.note_id], ids(&diff.left));
+        assert_eq!(vec![other.note_id], ids(&diff.right));
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn edit_note() {
+        let base_dir = "tmp/ztln_orga26";
+        let filename = "tmp/test26";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "This is test 26 content").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        orga.add_note_reference("main", "main~1").unwrap();
+        std::fs::write(filename, "Reworked ownership note").unwrap();
+        let edited = orga.edit_note("main~1", filename).unwrap();
+        assert_eq!((meta1.note_id, &meta1.parents, &meta1.references), (edited.note_id, &edited.parents, &edited.references));
+        assert_eq!(Some("Reworked ownership note".to_string()), edited.title);
+        assert_eq!("Reworked ownership note", orga.get_note_content(meta1.note_id).unwrap());
+        assert_eq!(meta1.note_id, orga.search("ownership", None, None, None).unwrap()[0].metadata.note_id);
+        let backlinks = orga.get_backlinks(&meta1).unwrap();
+        assert_eq!(vec![meta2.note_id], backlinks.iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>());
+        assert!(orga.get_backlinks(&meta2).unwrap().is_empty());
+        std::fs::write(filename, "").unwrap();
+        assert!(orga.edit_note("main", filename).is_err());
+        assert!(orga.edit_note("wrong", filename).is_err());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn store_errors() {
+        let base_dir = "tmp/ztln_orga27";
+        let filename = "tmp/test27";
+        let mut orga = Organization::new( Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "This is test 27 content").unwrap();
+        let meta = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(std::path::Path::new(base_dir).join("index"), "garbage").unwrap();
+        assert_eq!(ErrorKind::Corrupted, orga.list_keywords().unwrap_err().kind());
+        assert_eq!(ErrorKind::Corrupted, orga.add_keyword("tag", None).unwrap_err().kind());
+        std::fs::remove_file(std::path::Path::new(base_dir).join("notes").join(meta.note_id.to_string())).unwrap();
+        let error = orga.get_note_content(meta.note_id).unwrap_err();
+        assert_eq!(ErrorKind::Io, error.kind());
+        assert!(error.to_string().contains(&meta.note_id.to_string()), "the error tells which file failed");
+        std::fs::write(std::path::Path::new(base_dir).join("topics/topic1/paths/main"), "not an UUID").unwrap();
+        assert_eq!(ErrorKind::Corrupted, orga.solve_location("main").unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, orga.set_current_topic("wrong").unwrap_err().kind());
+        assert_eq!(ErrorKind::Conflict, orga.create_topic("topic1").unwrap_err().kind());
+        assert_eq!(ErrorKind::Conflict, Store::init(base_dir).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, Store::attach("tmp/ztln_orga27_wrong").unwrap_err().kind());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn templates() {
+        let base_dir = "tmp/ztln_orga28";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test28", "# Ownership\n\nEach value has an owner.").unwrap();
+        orga.add_note("tmp/test28", None, None).unwrap();
+        std::fs::create_dir(format!("{}/templates", base_dir)).unwrap();
+        std::fs::write(format!("{}/templates/literature.md", base_dir), "# {{topic}}/{{path}} after {{parent_title}}\n{{uuid}}").unwrap();
+        std::fs::write(format!("{}/daily.txt", base_dir), "{{date}} {{parent_title}}").unwrap();
+
+        let (note_id, content) = orga.render_template("literature", None, None).unwrap();
+        assert_eq!(format!("# topic1/main after Ownership\n{}", note_id), content);
+        std::fs::write("tmp/test28", &content).unwrap();
+        let metadata = orga.add_note_with_id(note_id, "tmp/test28", None, None).unwrap();
+        assert_eq!(note_id, metadata.note_id);
+        let (_, content) = orga.render_template("literature", None, Some("draft")).unwrap();
+        assert!(content.starts_with("# topic1/draft after topic1/main after Ownership\n"));
+
+        let mut config = Config::default();
+        config.templates.insert("daily".to_string(), PathBuf::from("daily.txt"));
+        orga.configure(&config).unwrap();
+        let (_, content) = orga.render_template("daily", None, None).unwrap();
+        assert_eq!(format!("{} topic1/main after Ownership", Utc::now().format("%Y-%m-%d")), content);
+        assert_eq!(ErrorKind::NotFound, orga.render_template("missing", None, None).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, orga.render_template("daily", Some("topic2"), None).unwrap_err().kind());
+        for name in ["../daily", "sub/literature", "..\\literature", ".."] {
+            assert_eq!(ErrorKind::Invalid, orga.render_template(name, None, None).unwrap_err().kind(), "{}", name);
+        }
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn front_matter() {
+        let base_dir = "tmp/ztln_orga29";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test29", "Ownership").unwrap();
+        let first = orga.add_note("tmp/test29", None, None).unwrap();
+        std::fs::write("tmp/test29", "---\ntitle: Borrowing\ntags: [rust, memory]\nrefs: [HEAD]\n---\nReferences borrow values.").unwrap();
+        let second = orga.add_note("tmp/test29", None, None).unwrap();
+        assert_eq!(vec![first.note_id], second.references);
+        assert_eq!(vec!["memory".to_string(), "rust".to_string()], orga.store.get_note_keywords(second.note_id).unwrap());
+        assert_eq!(second, orga.solve_location("HEAD").unwrap().unwrap());
+
+        std::fs::write("tmp/test29", "+++\ntags = [\"rust\", \"borrow\"]\nrefs = [\"HEAD\", \"main~1\"]\n+++\nReferences borrow values.").unwrap();
+        let edited = orga.edit_note("HEAD", "tmp/test29").unwrap();
+        assert_eq!(vec![first.note_id], edited.references);
+        assert_eq!(vec!["borrow".to_string(), "memory".to_string(), "rust".to_string()], orga.store.get_note_keywords(second.note_id).unwrap());
+        assert_eq!(1, orga.search_keyword("rust").unwrap().len());
+
+        std::fs::write("tmp/test29", "---\nrefs: [main~5]\n---\nLifetimes").unwrap();
+        assert_eq!(ErrorKind::NotFound, orga.add_note("tmp/test29", None, None).unwrap_err().kind());
+        std::fs::write("tmp/test29", "---\ntags: rust\n---\nLifetimes").unwrap();
+        assert_eq!(ErrorKind::Parse, orga.add_note("tmp/test29", None, None).unwrap_err().kind());
+        assert_eq!(2, orga.get_path_log(None, None).unwrap().len());
+
+        orga.create_topic("topic2").unwrap();
+        std::fs::write("tmp/test29", "---\nrefs: [HEAD]\n---\nLifetimes").unwrap();
+        assert_eq!(ErrorKind::NotFound, orga.add_note("tmp/test29", Some("topic2"), Some("newpath")).unwrap_err().kind());
+        assert_eq!(Some("topic1".to_string()), orga.get_current_topic().unwrap());
+        assert_eq!(vec!["main".to_string()], orga.get_paths_list(Some("topic1")).unwrap().1);
+        assert!(orga.get_paths_list(Some("topic2")).unwrap().1.is_empty());
+        let lifetimes = orga.add_note("tmp/test29", Some("topic1"), Some("newpath")).unwrap();
+        assert_eq!(vec![second.note_id], lifetimes.references, "HEAD is the note the new path branches from");
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn wiki_links() {
+        let base_dir = "tmp/ztln_orga30";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test30", "Ownership").unwrap();
+        let first = orga.add_note("tmp/test30", None, None).unwrap();
+        let short_uuid = orga.short_uuid(first.note_id).unwrap();
+        std::fs::write("tmp/test30", format!("After [[{}]], see [[HEAD|ownership]].", short_uuid)).unwrap();
+        let second = orga.add_note("tmp/test30", None, None).unwrap();
+        assert_eq!(vec![first.note_id], second.references);
+        assert_eq!(format!("After [[{}]], see [[HEAD|ownership]].", short_uuid), orga.get_note_content(second.note_id).unwrap());
+
+        orga.configure(&Config { rewrite_links: Some(true), ..Default::default() }).unwrap();
+        std::fs::write("tmp/test30", "Borrowing, see [[topic1/main:-1|ownership]] and [[HEAD]].").unwrap();
+        let edited = orga.edit_note("HEAD", "tmp/test30").unwrap();
+        assert_eq!(vec![first.note_id], edited.references);
+        assert_eq!(
+            format!("Borrowing, see [[{}|ownership]] and [[{}]].", first.note_id, second.note_id),
+            orga.get_note_content(second.note_id).unwrap()
+        );
+        assert_eq!("Borrowing, see [[topic1/main:-1|ownership]] and [[HEAD]].", std::fs::read_to_string("tmp/test30").unwrap());
+        assert_eq!(second.note_id, orga.search("borrowing", None, None, None).unwrap()[0].metadata.note_id);
+
+        assert!(orga.take_warnings().is_empty());
+        std::fs::write("tmp/test30", "See [[main~4]], [[A note to write]] and [[HEAD]], not `grid[[i]]`.").unwrap();
+        let third = orga.add_note("tmp/test30", None, None).unwrap();
+        assert_eq!(vec![second.note_id], third.references);
+        assert_eq!(
+            format!("See [[main~4]], [[A note to write]] and [[{}]], not `grid[[i]]`.", second.note_id),
+            orga.get_note_content(third.note_id).unwrap()
+        );
+        assert_eq!(2, orga.take_warnings().len());
+        assert!(orga.take_warnings().is_empty());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn wiki_links_without_topic() {
+        let base_dir = "tmp/ztln_orga35";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        std::fs::write("tmp/test35", "See [[foo]] and [[unknowntopic/x]].").unwrap();
+        let idea = orga.add_fleeting_note("tmp/test35").unwrap();
+        assert!(idea.references.is_empty());
+        assert_eq!("See [[foo]] and [[unknowntopic/x]].", orga.get_note_content(idea.note_id).unwrap());
+        assert_eq!(2, orga.take_warnings().len());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn titles() {
+        let base_dir = "tmp/ztln_orga31";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test31", "# Ownership rules\n\nEach value has an owner.").unwrap();
+        let rules = orga.add_note("tmp/test31", None, None).unwrap();
+        assert_eq!(Some("Ownership rules".to_string()), rules.title);
+        std::fs::write("tmp/test31", "---\ntitle: The ownership of   closures\n---\n# Closures").unwrap();
+        let closures = orga.add_note("tmp/test31", None, None).unwrap();
+        assert_eq!(Some("The ownership of closures".to_string()), closures.title);
+        std::fs::write("tmp/test31", "Borrowing").unwrap();
+        let borrowing = orga.add_note("tmp/test31", None, None).unwrap();
+
+        let solve = |orga: &mut Organization, expr: &str| orga.solve_location(expr).unwrap().map(|meta| meta.note_id);
+        assert_eq!(Some(rules.note_id), solve(&mut orga, "title:ownership rules"));
+        assert_eq!(Some(rules.note_id), solve(&mut orga, "title:ownership"));
+        assert_eq!(Some(closures.note_id), solve(&mut orga, "title:own clos"));
+        assert_eq!(Some(borrowing.note_id), solve(&mut orga, "title:brwng"));
+        assert_eq!(None, solve(&mut orga, "title:lifetimes"));
+
+        // notes stored before titles get the first line of their content
+        let mut metadata = orga.solve_location("HEAD").unwrap().unwrap();
+        metadata.title = None;
+        orga.store.write_note_metadata(&metadata).unwrap();
+        assert_eq!("Borrowing", orga.get_title(&metadata).unwrap());
+        assert_eq!(Some(borrowing.note_id), solve(&mut orga, "title:borrowing"));
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn fleeting_notes() {
+        let base_dir = "tmp/ztln_orga32";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test32", "Ownership").unwrap();
+        let first = orga.add_note("tmp/test32", None, None).unwrap();
+        std::fs::write("tmp/test32", "---\ntags: [rust]\n---\nIdea about [[HEAD]]").unwrap();
+        let idea = orga.add_fleeting_note("tmp/test32").unwrap();
+        assert_eq!(vec![first.note_id], idea.references);
+        std::fs::write("tmp/test32", "Another idea").unwrap();
+        let another = orga.add_fleeting_note("tmp/test32").unwrap();
+        let inbox = |orga: &Organization| {
+            let mut uuids = orga.get_inbox().unwrap().iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>();
+            uuids.sort();
+            uuids
+        };
+        let mut expected = vec![idea.note_id, another.note_id];
+        expected.sort();
+        assert_eq!(expected, inbox(&orga));
+        assert_eq!(first.note_id, orga.solve_location("HEAD").unwrap().unwrap().note_id, "fleeting notes are outside the paths");
+        assert!(orga.locate_note(&idea).unwrap().is_none());
+        assert_eq!(1, orga.grep("idea about", true, None).unwrap().len());
+
+        let short_uuid = orga.short_uuid(idea.note_id).unwrap();
+        let promoted = orga.promote_note(&short_uuid, Some("draft")).unwrap();
+        assert_eq!((NoteKind::Permanent, Some(idea.note_id), vec![first.note_id]), (promoted.kind, promoted.origin, promoted.references.clone()));
+        assert_eq!(("topic1", "draft"), (promoted.topic.as_str(), promoted.path.as_str()));
+        assert_eq!(vec![first.note_id], promoted.parents);
+        assert_eq!(promoted.note_id, orga.solve_location("#rust").unwrap().unwrap().note_id);
+        assert!(orga.solve_location(&short_uuid).unwrap().unwrap().archived);
+        assert_eq!(vec![another.note_id], inbox(&orga));
+        assert!(orga.promote_note(&short_uuid, None).is_err(), "already promoted");
+        assert!(orga.promote_note("HEAD", None).is_err(), "not a fleeting note");
+        assert!(orga.move_note(&another.note_id.to_string(), "main").is_err());
+        assert_eq!(ErrorKind::Conflict, orga.copy_note(&another.note_id.to_string(), "main").unwrap_err().kind());
+        assert_eq!(ErrorKind::Conflict, orga.cherry_pick(&another.note_id.to_string(), "main").unwrap_err().kind());
+        assert_eq!(vec![another.note_id], inbox(&orga), "refused copies leave the inbox unchanged");
+        assert_eq!(format!("inbox:{}", orga.short_uuid(another.note_id).unwrap()), orga.get_place(&another).unwrap());
+        assert_eq!("topic1/draft", orga.get_place(&promoted).unwrap());
+
+        let mut metadata = orga.solve_location("HEAD").unwrap().unwrap();
+        orga.set_note_kind(&mut metadata, NoteKind::Literature).unwrap();
+        assert_eq!(NoteKind::Literature, orga.solve_location("HEAD").unwrap().unwrap().kind);
+        assert!(orga.set_note_kind(&mut metadata, NoteKind::Fleeting).is_err());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn sources() {
+        let base_dir = "tmp/ztln_orga33";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test33.bib", "@book{ahrens2017, author = {Ahrens, Sönke}, title = {Smart Notes}, year = 2017}\n@misc{draft, title = {Draft}}").unwrap();
+        assert_eq!(2, orga.import_sources("tmp/test33.bib").unwrap().len());
+        std::fs::write("tmp/test33.bib", "@book{ahrens2017, author = {Ahrens, Sönke}, title = {How to Take Smart Notes}, year = 2017}").unwrap();
+        assert_eq!(1, orga.import_sources("tmp/test33.bib").unwrap().len());
+        assert_eq!(vec!["ahrens2017", "draft"], orga.get_sources().unwrap().iter().map(|source| source.citekey.as_str()).collect::<Vec<&str>>());
+
+        std::fs::write("tmp/test33", "Notes are written in one's own words").unwrap();
+        orga.add_note("tmp/test33", None, None).unwrap();
+        orga.add_note("tmp/test33", None, None).unwrap();
+        let cited = orga.cite_source("HEAD", "ahrens2017", Some("42")).unwrap();
+        assert_eq!(Some("Ahrens (2017), How to Take Smart Notes, p. 42".to_string()), orga.get_citation(&cited).unwrap());
+        let copy = orga.copy_note("HEAD", "other").unwrap();
+        let mut citing = orga.get_citing_notes("ahrens2017").unwrap().iter().map(|meta| meta.note_id).collect::<Vec<Uuid>>();
+        citing.sort();
+        let mut expected = vec![cited.note_id, copy.note_id];
+        expected.sort();
+        assert_eq!(expected, citing, "copies keep the source");
+        assert!(orga.get_citing_notes("draft").unwrap().is_empty());
+        let uncited = orga.solve_location("main~1").unwrap().unwrap();
+        assert_eq!(None, orga.get_citation(&uncited).unwrap());
+
+        assert_eq!(ErrorKind::NotFound, orga.cite_source("HEAD", "unknown", None).unwrap_err().kind());
+        let injected = format!("1\nreference: {}", uncited.note_id);
+        let cited = orga.cite_source("HEAD", "ahrens2017", Some(&injected)).unwrap();
+        assert_eq!(cited, orga.solve_location("HEAD").unwrap().unwrap());
+        assert_eq!(Some(format!("1 reference: {}", uncited.note_id)), cited.page);
+        assert!(cited.references.is_empty());
+        assert_eq!(ErrorKind::Invalid, orga.cite_source("HEAD", "ahrens2017", Some("4\u{7}2")).unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, orga.get_citing_notes("unknown").unwrap_err().kind());
+        std::fs::write("tmp/test33.bib", "@book{broken, title = {unbalanced}").unwrap();
+        assert_eq!(ErrorKind::Parse, orga.import_sources("tmp/test33.bib").unwrap_err().kind());
+        assert_eq!(2, orga.get_sources().unwrap().len());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+
+    #[test]
+    fn attachments() {
+        let base_dir = "tmp/ztln_orga34";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write("tmp/test34", "Figure of the paths").unwrap();
+        orga.add_note("tmp/test34", None, None).unwrap();
+        std::fs::write("tmp/test34.png", [0x89, 0x50, 0x4e, 0x47, 0x00, 0xff]).unwrap();
+        let (metadata, name) = orga.attach_file("HEAD", "tmp/test34.png").unwrap();
+        assert_eq!("test34.png", name);
+        std::fs::write("tmp/test34.png", [0x89, 0x50]).unwrap();
+        let (metadata, _) = orga.attach_file(&metadata.note_id.to_string(), "tmp/test34.png").unwrap();
+        assert_eq!(vec!["test34.png".to_string()], metadata.attachments);
+        let paths = orga.get_attachment_paths(&metadata);
+        assert_eq!(std::path::Path::new(base_dir).join("attachments").join(metadata.note_id.to_string()).join("test34.png"), paths[0]);
+        assert_eq!(vec![0x89, 0x50], std::fs::read(&paths[0]).unwrap(), "the attachment is replaced");
+        assert_eq!(metadata, orga.solve_location("HEAD").unwrap().unwrap());
+
+        let copy = orga.copy_note("HEAD", "other").unwrap();
+        assert_eq!(metadata.attachments, copy.attachments);
+        assert!(orga.get_attachment_paths(&copy)[0].is_file());
+        assert!(orga.attach_file("HEAD", "tmp/missing34.png").is_err());
+        let injected = format!("tmp/test34\nreference: {}", metadata.note_id);
+        std::fs::write(&injected, "not a figure").unwrap();
+        assert_eq!(ErrorKind::Invalid, orga.attach_file("HEAD", &injected).unwrap_err().kind());
+        std::fs::remove_file(&injected).unwrap();
+        assert_eq!(copy.attachments, orga.solve_location("HEAD").unwrap().unwrap().attachments);
+
+        assert!(orga.check_attachments().unwrap().is_empty());
+        let attachments = std::path::Path::new(base_dir).join("attachments");
+        std::fs::write(attachments.join(metadata.note_id.to_string()).join("stray.txt"), "stray").unwrap();
+        std::fs::remove_file(&orga.get_attachment_paths(&copy)[0]).unwrap();
+        let deleted = Uuid::new_v4();
+        std::fs::create_dir_all(attachments.join(deleted.to_string())).unwrap();
+        let mut expected = vec![
+            AttachmentProblem::Missing { note_id: copy.note_id, name: "test34.png".to_string() },
+            AttachmentProblem::Unlisted { note_id: metadata.note_id, name: "stray.txt".to_string() },
+            AttachmentProblem::Orphaned { note_id: deleted },
+        ];
+        let mut problems = orga.check_attachments().unwrap();
+        let order = |problem: &AttachmentProblem| problem.to_string();
+        problems.sort_by_key(order);
+        expected.sort_by_key(order);
+        assert_eq!(expected, problems);
+        assert_eq!(2, orga.prune_attachments(true).unwrap().len());
+        assert_eq!(3, orga.check_attachments().unwrap().len(), "nothing is removed by a dry run");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = std::ffi::OsStr::from_bytes(b"stray\xff.txt");
+            std::fs::write(attachments.join(metadata.note_id.to_string()).join(name), "stray").unwrap();
+        }
+        assert_eq!(2, orga.prune_attachments(false).unwrap().len());
+        assert_eq!(vec![AttachmentProblem::Missing { note_id: copy.note_id, name: "test34.png".to_string() }], orga.check_attachments().unwrap());
+        assert!(!attachments.join(deleted.to_string()).exists());
+        assert!(orga.get_attachment_paths(&metadata)[0].is_file());
+        assert_eq!(ErrorKind::NotFound, orga.attach_file("main~3", "tmp/test34.png").unwrap_err().kind());
+
+        std::fs::remove_dir_all(std::path::Path::new(base_dir)).unwrap();
+    }
+}
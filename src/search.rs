@@ -0,0 +1,241 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::note::NoteMetaData;
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+const SNIPPET_WIDTH: usize = 80;
+
+/**
+Inverted index of the notes contents. For each term, it keeps the number of
+occurences of that term in each note so results can be ranked using the BM25
+scoring function.
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    postings: HashMap<String, HashMap<Uuid, u32>>,
+    lengths: HashMap<Uuid, u32>,
+}
+
+impl SearchIndex {
+    /**
+     * Split a text in lowercased terms. Every non alphanumeric character is
+     * considered as a separator.
+     */
+    pub fn tokenize(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect()
+    }
+
+    /**
+     * Index (or re-index) the given note content.
+     */
+    pub fn index_note(&mut self, uuid: Uuid, content: &str) {
+        self.remove_note(uuid);
+        let terms = Self::tokenize(content);
+        self.lengths.insert(uuid, terms.len() as u32);
+        for term in terms {
+            *self.postings
+                .entry(term)
+                .or_default()
+                .entry(uuid)
+                .or_insert(0) += 1;
+        }
+    }
+
+    pub fn remove_note(&mut self, uuid: Uuid) {
+        if self.lengths.remove(&uuid).is_none() {
+            return;
+        }
+        self.postings.retain(|_, notes| {
+            notes.remove(&uuid);
+            !notes.is_empty()
+        });
+    }
+
+    /**
+     * Return the notes matching at least one term of the query with their
+     * BM25 score, best matches first.
+     */
+    pub fn search(&self, query: &str) -> Vec<(Uuid, f64)> {
+        let count = self.lengths.len() as f64;
+        if count == 0.0 {
+            return Vec::new();
+        }
+        let average_length = self.lengths.values().map(|l| *l as f64).sum::<f64>() / count;
+        let mut terms = Self::tokenize(query);
+        terms.sort();
+        terms.dedup();
+        let mut scores: HashMap<Uuid, f64> = HashMap::new();
+
+        for term in &terms {
+            if let Some(notes) = self.postings.get(term) {
+                let frequency = notes.len() as f64;
+                let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+                for (uuid, occurences) in notes {
+                    let occurences = *occurences as f64;
+                    let length = *self.lengths.get(uuid).unwrap_or(&0) as f64;
+                    let score = idf * occurences * (BM25_K1 + 1.0)
+                        / (occurences + BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length.max(1.0)));
+                    *scores.entry(*uuid).or_insert(0.0) += score;
+                }
+            }
+        }
+        let mut results: Vec<(Uuid, f64)> = scores.into_iter().collect();
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        results
+    }
+}
+
+/**
+A search result: the note, its score and an excerpt of its content around the
+first matching term.
+ */
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub metadata: NoteMetaData,
+    pub score: f64,
+    pub snippet: String,
+}
+
+/**
+A note matching a grep pattern with its location and the matching lines
+(numbered from 1).
+ */
+#[derive(Debug, Serialize)]
+pub struct GrepResult {
+    pub metadata: NoteMetaData,
+    pub location: String,
+    pub lines: Vec<(usize, String)>,
+}
+
+/**
+ * Extract a single line excerpt of the content centered on the first word
+ * matching one of the query terms.
+ */
+pub fn make_snippet(content: &str, query: &str) -> String {
+    let terms = SearchIndex::tokenize(query);
+    let chars: Vec<char> = content.chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    let mut position = 0;
+    let mut start = 0;
+    let mut found = false;
+    for (index, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            let word: String = chars[start..index].iter().collect();
+            if !word.is_empty() && terms.contains(&word.to_lowercase()) {
+                position = start;
+                found = true;
+                break;
+            }
+            start = index + 1;
+        }
+    }
+    if !found && start < chars.len() {
+        let word: String = chars[start..].iter().collect();
+        if terms.contains(&word.to_lowercase()) {
+            position = start;
+        }
+    }
+    let begin = position.saturating_sub(SNIPPET_WIDTH / 4)
+        .min(chars.len().saturating_sub(SNIPPET_WIDTH));
+    let end = (begin + SNIPPET_WIDTH).min(chars.len());
+    let mut snippet: String = chars[begin..end].iter().collect();
+    snippet = snippet.trim().to_string();
+    if begin > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+/**
+ * How well a title matches the words of a `title:` location, `None` when it
+ * does not match. The same words come first, then titles with words starting
+ * with the given ones and at last titles containing their letters in order.
+ * Ties are broken by the number of identical words and by shorter titles.
+ */
+pub fn title_score(query: &str, title: &str) -> Option<(u8, usize, Reverse<usize>)> {
+    let words = SearchIndex::tokenize(query);
+    let title_words = SearchIndex::tokenize(title);
+    if words.is_empty() {
+        return None;
+    }
+    let same = words.iter().filter(|word| title_words.contains(word)).count();
+    let tier = if words == title_words {
+        3
+    } else if words.iter().all(|word| title_words.iter().any(|title_word| title_word.starts_with(word.as_str()))) {
+        2
+    } else {
+        let mut letters = title.to_lowercase().chars().collect::<Vec<char>>().into_iter();
+        if !words.concat().chars().all(|c| letters.any(|letter| letter == c)) {
+            return None;
+        }
+        1
+    };
+
+    Some((tier, same, Reverse(title_words.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(vec!["rust", "s", "ownership", "rules"], SearchIndex::tokenize("Rust's ownership-rules!"));
+        assert!(SearchIndex::tokenize("  -- ").is_empty());
+    }
+
+    #[test]
+    fn search_ranking() {
+        let mut index = SearchIndex::default();
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+        let uuid3 = Uuid::new_v4();
+        index.index_note(uuid1, "borrow checker and ownership");
+        index.index_note(uuid2, "ownership ownership ownership");
+        index.index_note(uuid3, "nothing relevant here");
+        let results = index.search("Ownership");
+        assert_eq!(2, results.len());
+        assert_eq!(uuid2, results[0].0, "most relevant note first");
+        assert!(index.search("unknown").is_empty());
+        index.index_note(uuid2, "something else");
+        let results = index.search("ownership");
+        assert_eq!(1, results.len(), "re-indexing a note forgets its former content");
+        index.remove_note(uuid1);
+        assert!(index.search("ownership").is_empty());
+    }
+
+    #[test]
+    fn snippet() {
+        let content = "first line\nThe ownership rules are checked at compile time.";
+        assert_eq!("first line The ownership rules are checked at compile time.", make_snippet(content, "rules"));
+        let long = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+        let snippet = make_snippet(&long, "needle");
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("needle"));
+    }
+
+    #[test]
+    fn title_scores() {
+        let query = "ownership rules";
+        assert_eq!(Some((3, 2, Reverse(2))), title_score(query, "Ownership Rules"));
+        assert_eq!(Some((2, 2, Reverse(5))), title_score(query, "The ownership rules of Rust"));
+        assert_eq!(Some((2, 0, Reverse(2))), title_score("own rul", "Ownership rules"));
+        assert_eq!(Some((1, 0, Reverse(1))), title_score("ownrs", "ownership"));
+        assert_eq!(None, title_score(query, "Borrowing"));
+        assert_eq!(None, title_score(" ", "Borrowing"));
+        assert!(title_score(query, "Ownership Rules") > title_score(query, "The ownership rules of Rust"));
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+use uuid::Uuid;
+
+use crate::error::{ErrorKind, Result, ZtlnError};
+use crate::organization::Organization;
+use crate::store::Store;
+use crate::config::Config;
+use crate::json::{topic_to_json, path_to_json, note_to_json};
+
+/**
+A request to the JSON API: the method, the decoded path segments, the query
+parameters, the declared type of the body and the JSON body (null when
+empty).
+ */
+#[derive(Debug)]
+pub struct ApiRequest {
+    pub method: String,
+    pub segments: Vec<String>,
+    pub query: HashMap<String, String>,
+    pub content_type: Option<String>,
+    pub body: Value,
+}
+
+impl ApiRequest {
+    /**
+     * Build a request from an URL as sent by HTTP clients (`/path?query`).
+     */
+    pub fn new(method: &str, url: &str, body: &str) -> Result<Self> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let segments = path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| decode(segment, false))
+            .collect();
+        let query = query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key, true), decode(value, true))
+            })
+            .collect();
+        let body = if body.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(body).map_err(|e| ZtlnError::Default(format!("Invalid JSON body: {}", e)))?
+        };
+
+        Ok(Self { method: method.to_uppercase(), segments, query, content_type: None, body })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(|value| value.as_str())
+    }
+
+    fn field(&self, name: &str) -> Option<&str> {
+        self.body.get(name).and_then(|value| value.as_str())
+    }
+
+    fn required_field(&self, name: &str) -> Result<&str> {
+        self.field(name)
+            .ok_or_else(|| ZtlnError::Default(format!("Missing field '{}' in request body.", name)))
+    }
+}
+
+/**
+A response of the JSON API: an HTTP status and a JSON body.
+ */
+#[derive(Debug, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self { status, body: json!({ "error": message }) }
+    }
+}
+
+/**
+ * Serve the JSON API of the organization until the process is stopped. Each
+ * request is handled with a freshly attached store so changes made by other
+ * processes are seen.
+ */
+pub fn serve(base_dir: &str, config: &Config, address: &str, port: u16, read_only: bool) -> Result<()> {
+    Store::attach(base_dir)?;
+    let server = Server::http((address, port))
+        .map_err(|e| ZtlnError::Default(format!("Cannot listen on {}:{} ({})", address, port, e)))?;
+    for mut request in server.incoming_requests() {
+        let header = |name: &'static str| request.headers().iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.to_string());
+        let content_type = header("Content-Type");
+        let trusted = is_trusted(header("Host").as_deref(), header("Origin").as_deref(), address, port);
+        let mut body = String::new();
+        let response = if !trusted {
+            ApiResponse::error(403, "The host or the origin of the request is not local.")
+        } else {
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => match ApiRequest::new(&request.method().to_string(), request.url(), &body) {
+                    Ok(api_request) => handle(base_dir, config, &ApiRequest { content_type, ..api_request }, read_only),
+                    Err(e) => ApiResponse::error(400, &e.to_string()),
+                },
+                Err(e) => ApiResponse::error(400, &e.to_string()),
+            }
+        };
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let http_response = Response::from_string(response.body.to_string())
+            .with_status_code(response.status)
+            .with_header(header);
+        // a client closing the connection early must not stop the server
+        let _ = request.respond(http_response);
+    }
+
+    Ok(())
+}
+
+/**
+ * Route a request to the organization.
+ */
+pub fn handle(base_dir: &str, config: &Config, request: &ApiRequest, read_only: bool) -> ApiResponse {
+    if request.method != Method::Get.as_str() && read_only {
+        return ApiResponse::error(403, "The server is in read-only mode.");
+    }
+    // browsers send other types from any page without asking the server first
+    let json = request.content_type.as_deref()
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"));
+    if request.method != Method::Get.as_str() && !json {
+        return ApiResponse::error(415, "The body of the request must be of type application/json.");
+    }
+    let mut orga = Organization::new(match Store::attach(base_dir) {
+        Ok(store) => store,
+        Err(e) => return ApiResponse::error(500, &e.to_string()),
+    });
+    if let Err(e) = orga.configure(config) {
+        return ApiResponse::error(500, &e.to_string());
+    }
+    match route(&mut orga, request) {
+        Ok(Some(body)) => ApiResponse::ok(body),
+        Ok(None) => ApiResponse::error(404, "Not found."),
+        Err(e) => match e.kind() {
+            ErrorKind::NotFound => ApiResponse::error(404, &e.to_string()),
+            ErrorKind::Conflict => ApiResponse::error(409, &e.to_string()),
+            ErrorKind::Io | ErrorKind::Corrupted => ApiResponse::error(500, &e.to_string()),
+            ErrorKind::Invalid | ErrorKind::Parse => ApiResponse::error(400, &e.to_string()),
+        },
+    }
+}
+
+fn route(orga: &mut Organization, request: &ApiRequest) -> Result<Option<Value>> {
+    let segments: Vec<&str> = request.segments.iter().map(|segment| segment.as_str()).collect();
+    let value = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["topics"]) => {
+            let mut topics = Vec::new();
+            for topic in orga.get_topics_list(request.param("all") == Some("true"))? {
+                topics.push(topic_to_json(orga, &topic)?);
+            }
+            Value::Array(topics)
+        },
+        ("POST", ["topics"]) => {
+            let topic = request.required_field("name")?;
+            match request.field("main_path") {
+                Some(main_path) => orga.create_topic_with_main_path(topic, main_path)?,
+                None => orga.create_topic(topic)?,
+            }
+            if let Some(description) = request.field("description") {
+                orga.set_topic_description(topic, description)?;
+            }
+            topic_to_json(orga, topic)?
+        },
+        ("GET", ["topics", topic]) => {
+            orga.get_current_path(topic)?;
+            topic_to_json(orga, topic)?
+        },
+        ("GET", ["topics", topic, "paths"]) => {
+            let (topic, list) = orga.get_paths_list(Some(topic))?;
+            let mut paths = Vec::new();
+            for path in &list {
+                paths.push(path_to_json(orga, &topic, path)?);
+            }
+            Value::Array(paths)
+        },
+        ("POST", ["topics", topic, "paths"]) => {
+            let path = request.required_field("name")?;
+            let location = request.field("location").map_or_else(|| format!("{}/HEAD", topic), |l| l.to_string());
+            orga.create_path(path, Some(&location))?;
+            path_to_json(orga, topic, path)?
+        },
+        ("GET", ["notes"]) => {
+            let location = request.param("location").unwrap_or("HEAD");
+            let metadata = match orga.solve_location(location)? {
+                Some(metadata) => metadata,
+                None => return Ok(None),
+            };
+            note_to_json(orga, &metadata)?
+        },
+        ("POST", ["notes"]) => {
+            let content = request.required_field("content")?;
+            let filename = std::env::temp_dir().join(format!("ztln-{}", Uuid::new_v4()));
+            std::fs::write(&filename, content)?;
+            let result = orga.add_note(filename.to_str().unwrap_or(""), request.field("topic"), request.field("path"));
+            std::fs::remove_file(&filename)?;
+            json!(result?)
+        },
+        ("POST", ["references"]) => {
+            json!(orga.add_note_reference(request.required_field("from")?, request.required_field("to")?)?)
+        },
+        ("GET", ["tags"]) => {
+            let keywords: Vec<Value> = orga.list_keywords()?.iter()
+                .map(|(keyword, count)| json!({ "keyword": keyword, "count": count }))
+                .collect();
+            Value::Array(keywords)
+        },
+        ("GET", ["tags", keyword]) => json!(orga.search_keyword(keyword)?),
+        ("POST", ["tags"]) => {
+            let keyword = request.required_field("keyword")?;
+            let location = request.field("location").unwrap_or("HEAD");
+            orga.add_keyword(keyword, Some(location))?;
+            json!({ "keyword": keyword, "note": orga.solve_location(location)? })
+        },
+        ("GET", ["search"]) => {
+            let query = request.param("q").unwrap_or("");
+            let limit = request.param("limit").map_or(Ok(10), |limit| limit.parse::<usize>())
+                .map_err(|e| ZtlnError::Default(format!("Invalid limit: {}", e)))?;
+            let results = orga.search(query, request.param("topic"), request.param("path"), request.param("tag"))?;
+            json!(results.iter().take(limit).collect::<Vec<_>>())
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/**
+ * Any page open in a browser may send requests to the server: the `Host`
+ * must be the bound address or `localhost`, never a domain name which could
+ * be rebound to it, and the `Origin`, when given, must be local too.
+ */
+fn is_trusted(host: Option<&str>, origin: Option<&str>, address: &str, port: u16) -> bool {
+    let origin_trusted = match origin {
+        None => true,
+        Some(origin) => origin.split_once("://")
+            .is_some_and(|(_, authority)| is_local_authority(authority, address, port)),
+    };
+
+    origin_trusted && host.is_some_and(|host| is_local_authority(host, address, port))
+}
+
+/**
+ * Whether a `NAME[:PORT]` authority reaches the server bound on the address
+ * and the port.
+ */
+fn is_local_authority(authority: &str, address: &str, port: u16) -> bool {
+    let (name, authority_port) = match authority.rsplit_once(':') {
+        Some((name, authority_port)) if !authority_port.contains(']') => (name, authority_port.parse::<u16>().ok()),
+        _ => (authority, Some(80)),
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let bound = address.parse::<IpAddr>().ok();
+    let local_name = match (name.parse::<IpAddr>().ok(), bound) {
+        (Some(ip), Some(bound)) => bound.is_unspecified() || ip == bound || (ip.is_loopback() && bound.is_loopback()),
+        (Some(ip), None) => ip.is_loopback(),
+        (None, bound) => name.eq_ignore_ascii_case("localhost")
+            && bound.is_none_or(|bound| bound.is_loopback() || bound.is_unspecified()),
+    };
+
+    local_name && authority_port == Some(port)
+}
+
+/**
+ * Decode the `%XX` sequences of an URL component, `+` stands for a space in
+ * query strings.
+ */
+fn decode(text: &str, query: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len() && bytes[index + 1].is_ascii_hexdigit() && bytes[index + 2].is_ascii_hexdigit() => {
+                decoded.push(u8::from_str_radix(&text[index + 1..index + 3], 16).unwrap());
+                index += 2;
+            },
+            b'+' if query => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str, body: &str) -> ApiRequest {
+        let request = ApiRequest::new(method, url, body).unwrap();
+        ApiRequest { content_type: Some("application/json; charset=utf-8".to_string()), ..request }
+    }
+
+    #[test]
+    fn decode_url() {
+        assert_eq!("main~1", decode("main%7E1", false));
+        assert_eq!("a+b", decode("a+b", false));
+        assert_eq!("a b", decode("a+b", true));
+        assert_eq!("100%", decode("100%", true));
+        let req = request("get", "/tags/rust%20lang?location=topic%2Fmain%5E2&q=a+b", "");
+        assert_eq!("GET", req.method);
+        assert_eq!(vec!["tags", "rust lang"], req.segments);
+        assert_eq!(Some("topic/main^2"), req.param("location"));
+        assert_eq!(Some("a b"), req.param("q"));
+        assert!(ApiRequest::new("POST", "/notes", "{ not json").is_err());
+    }
+
+    #[test]
+    fn api() {
+        let base_dir = "tmp/ztln_server1";
+        Store::init(base_dir).unwrap();
+        let config = Config::default();
+        let ok = |response: ApiResponse| {
+            assert_eq!(200, response.status, "{:?}", response.body);
+            response.body
+        };
+        let topic = ok(handle(base_dir, &config, &request("POST", "/topics", r#"{"name": "topic1", "description": "About"}"#), false));
+        assert_eq!("About", topic["description"]);
+        assert_eq!(1, ok(handle(base_dir, &config, &request("GET", "/topics", ""), false)).as_array().unwrap().len());
+        let note = ok(handle(base_dir, &config, &request("POST", "/notes", r#"{"content": "ownership rules"}"#), false));
+        assert_eq!("main", note["path"]);
+        let shown = ok(handle(base_dir, &config, &request("GET", "/notes?location=topic1%2Fmain", ""), false));
+        assert_eq!("ownership rules", shown["content"]);
+        assert_eq!(note, shown["metadata"]);
+        ok(handle(base_dir, &config, &request("POST", "/tags", r#"{"keyword": "rust"}"#), false));
+        assert_eq!(note, ok(handle(base_dir, &config, &request("GET", "/tags/rust", ""), false))[0]);
+        let results = ok(handle(base_dir, &config, &request("GET", "/search?q=ownership", ""), false));
+        assert_eq!(note, results[0]["metadata"]);
+        let paths = ok(handle(base_dir, &config, &request("GET", "/topics/topic1/paths", ""), false));
+        assert_eq!(note["note_id"], paths[0]["head"]);
+        assert_eq!(404, handle(base_dir, &config, &request("GET", "/notes?location=wrong", ""), false).status);
+        assert_eq!(404, handle(base_dir, &config, &request("GET", "/topics/wrong", ""), false).status);
+        assert_eq!(404, handle(base_dir, &config, &request("GET", "/unknown", ""), false).status);
+        assert_eq!(400, handle(base_dir, &config, &request("POST", "/notes", "{}"), false).status);
+        let refused = handle(base_dir, &config, &request("POST", "/topics", r#"{"name": "topic2"}"#), true);
+        assert_eq!(403, refused.status);
+        assert_eq!(1, ok(handle(base_dir, &config, &request("GET", "/topics", ""), true)).as_array().unwrap().len());
+        let text_plain = ApiRequest {
+            content_type: Some("text/plain".to_string()),
+            ..ApiRequest::new("POST", "/topics", r#"{"name": "topic2"}"#).unwrap()
+        };
+        assert_eq!(415, handle(base_dir, &config, &text_plain, false).status);
+        assert_eq!(415, handle(base_dir, &config, &ApiRequest::new("POST", "/topics", r#"{"name": "topic2"}"#).unwrap(), false).status);
+        assert_eq!(1, ok(handle(base_dir, &config, &request("GET", "/topics", ""), false)).as_array().unwrap().len());
+
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn trusted_requests() {
+        assert!(is_trusted(Some("127.0.0.1:8080"), None, "127.0.0.1", 8080));
+        assert!(is_trusted(Some("localhost:8080"), Some("http://localhost:8080"), "127.0.0.1", 8080));
+        assert!(is_trusted(Some("[::1]:8080"), None, "::1", 8080));
+        assert!(is_trusted(Some("192.168.1.2:8080"), None, "0.0.0.0", 8080));
+        assert!(!is_trusted(None, None, "127.0.0.1", 8080));
+        assert!(!is_trusted(Some("attacker.example:8080"), None, "127.0.0.1", 8080), "DNS rebinding");
+        assert!(!is_trusted(Some("127.0.0.1"), None, "127.0.0.1", 8080));
+        assert!(!is_trusted(Some("192.168.1.2:8080"), None, "127.0.0.1", 8080));
+        assert!(!is_trusted(Some("127.0.0.1:8080"), Some("https://attacker.example"), "127.0.0.1", 8080));
+        assert!(!is_trusted(Some("127.0.0.1:8080"), Some("null"), "127.0.0.1", 8080));
+    }
+}
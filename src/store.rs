@@ -1,34 +1,23 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::fmt;
 use uuid::Uuid;
 use std::collections::HashMap;
+use std::cell::RefCell;
+use std::time::SystemTime;
+use chrono::{Utc, SubsecRound};
 
-use crate::{note::NoteMetaData, error::Result};
+use crate::{note::{NoteMetaData, NoteKind}, bibtex::Source, error::{Result, ZtlnError, IoContext}, search::SearchIndex};
+use serde::{Serialize, de::DeserializeOwned};
 
 /**
-This kind of problems raise the impossibility to perform the task because of
-physical layer. Keep in mind that theses errors are caught and they make the
-program to *panic* so use them with care with a nice & meaningful error message.
+Notes are displayed with at least this number of UUID characters.
  */
-#[derive(Debug)]
-pub struct StoreError {
-    message: String,
-}
-
-impl StoreError {
-    pub fn new(message: String) -> Self {
-        Self { message }
-    }
-}
-
-impl fmt::Display for StoreError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "STORE I/O ERROR → {}", self.message)
-    }
-}
+pub const SHORT_UUID_LENGTH: usize = 8;
 
-impl std::error::Error for StoreError {}
+/**
+Short UUIDs shorter than this are refused.
+ */
+pub const MIN_SHORT_UUID_LENGTH: usize = 4;
 
 
 /**
@@ -41,6 +30,13 @@ pub trait IOStore {
     fn set_current_topic(&self, topic: &str) -> Result<()>;
     fn get_current_topic(&self) -> Result<Option<String>>;
     fn topic_exists(&self, topic: &str) -> bool;
+    fn rename_topic(&self, topic: &str, new_topic: &str) -> Result<()>;
+    fn remove_topic(&self, topic: &str) -> Result<()>;
+    fn unset_current_topic(&self) -> Result<()>;
+    fn set_topic_archived(&self, topic: &str, archived: bool) -> Result<()>;
+    fn is_topic_archived(&self, topic: &str) -> bool;
+    fn get_topic_description(&self, topic: &str) -> Result<Option<String>>;
+    fn set_topic_description(&self, topic: &str, description: Option<&str>) -> Result<()>;
 
     fn get_paths(&self, topic: &str) -> Result<Vec<String>>;
     fn get_path(&self, topic: &str, path: &str) -> Result<Uuid>;
@@ -50,45 +46,70 @@ pub trait IOStore {
     fn get_current_path(&self, topic: &str) -> Result<Option<String>>;
     fn remove_path(&self, topic: &str, path: &str) -> Result<()>;
     fn reset_path(&self, topic: &str, path: &str, uuid: Uuid) -> Result<()>;
+    fn rename_path(&self, topic: &str, path: &str, new_path: &str) -> Result<()>;
 
     fn add_note(&self, topic: &str, path: &str, filename: &str) -> Result<NoteMetaData>;
+    fn add_note_with_id(&self, note_id: Uuid, topic: &str, path: &str, filename: &str) -> Result<NoteMetaData>;
     fn update_note_content(&self, filename: &str, note_id: Uuid) -> Result<()>;
+    fn write_note_content(&self, note_id: Uuid, content: &str) -> Result<()>;
     fn get_note_content(&self, uuid: Uuid) -> Result<String>;
+    fn copy_note_content(&self, from: Uuid, to: Uuid) -> Result<()>;
     fn get_note_metadata(&self, uuid: Uuid) -> Result<Option<NoteMetaData>>;
     fn write_note_metadata(&self, meta: &NoteMetaData) -> Result<()>;
     fn search_short_uuid(&self, short_uuid: &str) -> Result<Option<NoteMetaData>>;
+    fn remove_note(&self, uuid: Uuid) -> Result<()>;
+    fn get_notes(&self) -> Result<Vec<Uuid>>;
+    fn shortest_unique_prefix(&self, uuid: Uuid) -> Result<String>;
+    fn get_template(&self, file: &Path) -> Result<Option<String>>;
+    fn get_sources(&self) -> Result<Vec<Source>>;
+    fn write_sources(&self, sources: &[Source]) -> Result<()>;
+    fn get_attachment_path(&self, note_id: Uuid, name: &str) -> PathBuf;
+    fn write_attachment(&self, note_id: Uuid, name: &str, filename: &Path) -> Result<()>;
+    fn copy_attachments(&self, from: Uuid, to: Uuid) -> Result<()>;
+    fn get_attached_notes(&self) -> Result<Vec<Uuid>>;
+    fn get_attachment_names(&self, note_id: Uuid) -> Result<Vec<String>>;
+    fn remove_attachment(&self, note_id: Uuid, name: &str) -> Result<()>;
+    fn remove_attachments(&self, note_id: Uuid) -> Result<()>;
 
     fn add_keyword_index(&self, keyword: &str, metadata: &NoteMetaData) -> Result<()>;
     fn get_meta_from_index(&self, keyword: &str) -> Result<Vec<NoteMetaData>>;
     fn get_keywords(&self) -> Result<Vec<(String, usize)>>;
+    fn get_note_keywords(&self, uuid: Uuid) -> Result<Vec<String>>;
+
+    fn search_notes(&self, query: &str) -> Result<Vec<(Uuid, f64)>>;
 }
 
 #[derive(Debug)]
 pub struct Store<'a> {
     base_dir: &'a str,
+    /// UUIDs of the notes with the modification time of the `meta` directory
+    notes: RefCell<Option<(SystemTime, Vec<Uuid>)>>,
+    /// search index kept in memory during a bulk operation, see `hold_search_index`
+    search_index: RefCell<Option<SearchIndex>>,
 }
 
 impl<'a> Store<'a> {
     pub fn init(base_dir: &'a str) -> Result<Self> {
         let path = Path::new(base_dir);
         if path.exists() {
-            return Err(From::from(StoreError::new(format!("Given directory '{}' already exists.", base_dir))));
+            return Err(ZtlnError::OrganizationAlreadyExists(base_dir.to_string()));
+        }
+        fs::create_dir_all(base_dir).context("create directory", path)?;
+        for directory in &["meta", "notes", "topics"] {
+            fs::create_dir(path.join(directory)).context("create directory", path.join(directory))?;
         }
-        fs::create_dir_all(base_dir)?;
-        fs::create_dir(path.join("meta"))?;
-        fs::create_dir(path.join("notes"))?;
-        fs::create_dir(path.join("topics"))?;
 
         let index:HashMap<String, Vec<Uuid>> = HashMap::new();
-        fs::write(path.join("index"), bincode::serialize(&index)?)?;
+        write_binary(&path.join("index"), &index)?;
+        write_binary(&path.join("search_index"), &SearchIndex::default())?;
 
-        Ok(Self { base_dir })
+        Ok(Self { base_dir, notes: RefCell::new(None), search_index: RefCell::new(None) })
     }
 
     pub fn attach(base_dir: &'a str) -> Result<Self> {
         let path = Path::new(base_dir);
         if !path.is_dir() {
-            return Err(From::from(StoreError::new(format!("Given path '{}' is not a directory.", base_dir))));
+            return Err(ZtlnError::OrganizationDoesNotExist(base_dir.to_string()));
         }
 
         if !(
@@ -97,10 +118,10 @@ impl<'a> Store<'a> {
             && path.join("index").is_file()
             && path.join("topics").is_dir()
             ) {
-            return Err(From::from(StoreError::new(format!("Invalid ztln structure in dir '{}'.", base_dir))))
+            return Err(ZtlnError::Corrupted(path.to_path_buf(), "invalid ztln structure".to_string()))
         }
 
-        Ok( Self { base_dir })
+        Ok( Self { base_dir, notes: RefCell::new(None), search_index: RefCell::new(None) })
     }
 
     fn get_basedir_pathbuf(&self) -> PathBuf {
@@ -122,10 +143,117 @@ impl<'a> Store<'a> {
     }
 
     fn get_index(&self) -> Result<HashMap<String, Vec<Uuid>>> {
-        let index: HashMap<String, Vec<Uuid>> = bincode::deserialize(fs::read(self.get_basedir_pathbuf().join("index"))?.as_slice())?;
+        read_binary(&self.get_basedir_pathbuf().join("index"))
+    }
+
+    /**
+     * Forget the notes kept in memory, they are read again on next use.
+     */
+    pub fn reload(&self) {
+        self.notes.replace(None);
+    }
+
+    /**
+     * Run the given function on the sorted list of notes UUIDs. This list is
+     * loaded from the `meta` directory and kept in memory until the store
+     * writes a note or the directory is modified by another process.
+     */
+    fn with_notes_index<T>(&self, f: impl FnOnce(&[Uuid]) -> T) -> Result<T> {
+        let pathbuf = self.get_basedir_pathbuf().join("meta");
+        let modified = fs::metadata(&pathbuf)
+            .and_then(|metadata| metadata.modified())
+            .context("read", &pathbuf)?;
+        let mut notes = self.notes.borrow_mut();
+        if notes.as_ref().is_none_or(|(time, _)| *time != modified) {
+            let mut list = Vec::new();
+            for entry in fs::read_dir(&pathbuf).context("list", &pathbuf)? {
+                if let Some(Ok(uuid)) = entry.context("list", &pathbuf)?.file_name().to_str().map(Uuid::parse_str) {
+                    list.push(uuid);
+                }
+            }
+            list.sort();
+            *notes = Some((modified, list));
+        }
+
+        Ok(f(&notes.as_ref().unwrap().1))
+    }
+
+    /**
+     * Return the UUIDs starting with the given prefix. Since UUIDs are sorted
+     * in the same order as their textual representation, the matching ones
+     * are contiguous.
+     */
+    fn find_prefix(&self, prefix: &str) -> Result<Vec<Uuid>> {
+        self.with_notes_index(|notes| {
+            let start = notes.partition_point(|uuid| uuid.to_string().as_str() < prefix);
+            notes[start..].iter()
+                .take_while(|uuid| uuid.to_string().starts_with(prefix))
+                .cloned()
+                .collect()
+        })
+    }
+
+    /**
+     * Load the full text search index. Organizations created before the
+     * index existed get it built from the notes contents on first use.
+     */
+    fn get_search_index(&self) -> Result<SearchIndex> {
+        let pathbuf = self.get_basedir_pathbuf().join("search_index");
+        if pathbuf.is_file() {
+            return read_binary(&pathbuf);
+        }
+        let mut index = SearchIndex::default();
+        let notes_pathbuf = self.get_basedir_pathbuf().join("notes");
+        for entry in fs::read_dir(&notes_pathbuf).context("list", &notes_pathbuf)? {
+            let entry = entry.context("list", &notes_pathbuf)?;
+            if let Some(Ok(uuid)) = entry.file_name().to_str().map(Uuid::parse_str) {
+                index.index_note(uuid, &fs::read_to_string(entry.path()).context("read", entry.path())?);
+            }
+        }
+        self.write_search_index(&index)?;
 
         Ok(index)
+    }
 
+    fn write_search_index(&self, index: &SearchIndex) -> Result<()> {
+        write_binary(&self.get_basedir_pathbuf().join("search_index"), index)
+    }
+
+    /**
+     * Keep the search index in memory until `release_search_index` so that a
+     * bulk operation loads and writes it once instead of once per note.
+     */
+    pub fn hold_search_index(&self) -> Result<()> {
+        if self.search_index.borrow().is_none() {
+            let index = self.get_search_index()?;
+            self.search_index.replace(Some(index));
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Write the search index kept in memory by `hold_search_index`.
+     */
+    pub fn release_search_index(&self) -> Result<()> {
+        match self.search_index.take() {
+            Some(index) => self.write_search_index(&index),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * Change the search index, the one kept in memory if it is held, the
+     * file otherwise.
+     */
+    fn update_search_index(&self, f: impl FnOnce(&mut SearchIndex)) -> Result<()> {
+        if let Some(index) = self.search_index.borrow_mut().as_mut() {
+            f(index);
+            return Ok(());
+        }
+        let mut index = self.get_search_index()?;
+        f(&mut index);
+        self.write_search_index(&index)
     }
 }
 
@@ -133,15 +261,15 @@ impl<'a> IOStore for Store<'a> {
     fn get_current_topic(&self) -> Result<Option<String>> {
         let pathbuf = self.get_basedir_pathbuf().join("_CURRENT");
 
-        Ok(if pathbuf.is_file() { Some(fs::read_to_string(pathbuf)?) } else { None })
+        Ok(if pathbuf.is_file() { Some(fs::read_to_string(&pathbuf).context("read", &pathbuf)?) } else { None })
     }
 
     fn get_topics(&self) -> Result<Vec<String>> {
         let path = self.get_basedir_pathbuf().join("topics");
         let mut topics = Vec::new();
 
-        for entry in fs::read_dir(path)? {
-            let filename = entry?.file_name().to_str().unwrap_or("").to_string();
+        for entry in fs::read_dir(&path).context("list", &path)? {
+            let filename = entry.context("list", &path)?.file_name().to_str().unwrap_or("").to_string();
             if !filename.is_empty() {
                 topics.push(filename);
             }
@@ -152,14 +280,15 @@ impl<'a> IOStore for Store<'a> {
     }
 
     fn create_topic(&self, topic: &str) -> Result<()> {
-        fs::create_dir_all(self.get_topic_pathbuf(topic).join("paths"))?;
+        let pathbuf = self.get_topic_pathbuf(topic).join("paths");
+        fs::create_dir_all(&pathbuf).context("create directory", &pathbuf)?;
 
         Ok(())
     }
 
     fn set_current_topic(&self, topic: &str) -> Result<()> {
         let file_path = self.get_basedir_pathbuf().join("_CURRENT");
-        fs::write(file_path, topic)?;
+        fs::write(&file_path, topic).context("write", &file_path)?;
 
         Ok(())
     }
@@ -168,12 +297,70 @@ impl<'a> IOStore for Store<'a> {
       self.get_topic_pathbuf(topic).exists()  
     }
 
+    fn rename_topic(&self, topic: &str, new_topic: &str) -> Result<()> {
+        let pathbuf = self.get_topic_pathbuf(topic);
+        fs::rename(&pathbuf, self.get_topic_pathbuf(new_topic)).context("rename", &pathbuf)?;
+
+        Ok(())
+    }
+
+    fn remove_topic(&self, topic: &str) -> Result<()> {
+        let pathbuf = self.get_topic_pathbuf(topic);
+        fs::remove_dir_all(&pathbuf).context("remove", &pathbuf)?;
+
+        Ok(())
+    }
+
+    fn unset_current_topic(&self) -> Result<()> {
+        let pathbuf = self.get_basedir_pathbuf().join("_CURRENT");
+        if pathbuf.exists() {
+            fs::remove_file(&pathbuf).context("remove", &pathbuf)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_topic_archived(&self, topic: &str, archived: bool) -> Result<()> {
+        let pathbuf = self.get_topic_pathbuf(topic).join("_ARCHIVED");
+        if archived {
+            fs::write(&pathbuf, "").context("write", &pathbuf)?;
+        } else if pathbuf.exists() {
+            fs::remove_file(&pathbuf).context("remove", &pathbuf)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_topic_archived(&self, topic: &str) -> bool {
+        self.get_topic_pathbuf(topic).join("_ARCHIVED").exists()
+    }
+
+    fn get_topic_description(&self, topic: &str) -> Result<Option<String>> {
+        let pathbuf = self.get_topic_pathbuf(topic).join("description");
+        if pathbuf.exists() {
+            Ok(Some(fs::read_to_string(&pathbuf).context("read", &pathbuf)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn set_topic_description(&self, topic: &str, description: Option<&str>) -> Result<()> {
+        let pathbuf = self.get_topic_pathbuf(topic).join("description");
+        match description {
+            Some(text) => fs::write(&pathbuf, text).context("write", &pathbuf)?,
+            None if pathbuf.exists() => fs::remove_file(&pathbuf).context("remove", &pathbuf)?,
+            None => (),
+        }
+
+        Ok(())
+    }
+
     fn get_paths(&self, topic: &str) -> Result<Vec<String>> {
         let pathbuf = self.get_topic_pathbuf(topic).join("paths");
         let mut paths = Vec::new();
 
-        for entry in fs::read_dir(pathbuf)? {
-            let filename = entry?.file_name().to_str().unwrap_or("").to_string();
+        for entry in fs::read_dir(&pathbuf).context("list", &pathbuf)? {
+            let filename = entry.context("list", &pathbuf)?.file_name().to_str().unwrap_or("").to_string();
             if !filename.is_empty() {
                 paths.push(filename);
             }
@@ -184,20 +371,23 @@ impl<'a> IOStore for Store<'a> {
     }
 
     fn get_path(&self, topic: &str, path: &str) -> Result<Uuid> {
-        let uuid = Uuid::parse_str(fs::read_to_string(self.get_path_pathbuf(topic, path))?.as_str())?;
+        let pathbuf = self.get_path_pathbuf(topic, path);
+        let content = fs::read_to_string(&pathbuf).context("read", &pathbuf)?;
 
-        Ok(uuid)
+        Uuid::parse_str(content.as_str())
+            .map_err(|e| ZtlnError::Corrupted(pathbuf, e.to_string()))
     }
 
     fn write_path(&self, topic: &str, path: &str, uuid: Uuid) -> Result<()> {
-        fs::write(self.get_path_pathbuf(topic, path), uuid.to_string())?;
+        let pathbuf = self.get_path_pathbuf(topic, path);
+        fs::write(&pathbuf, uuid.to_string()).context("write", &pathbuf)?;
         
         Ok(())
     }
 
     fn set_current_path(&self, topic: &str, path: &str) -> Result<()> {
         let pathbuf = self.get_topic_pathbuf(topic).join("_HEAD");
-        fs::write(pathbuf, path)?;
+        fs::write(&pathbuf, path).context("write", &pathbuf)?;
 
         Ok(())
     }
@@ -205,7 +395,7 @@ impl<'a> IOStore for Store<'a> {
     fn get_current_path(&self, topic: &str) -> Result<Option<String>> {
         let pathbuf = self.get_topic_pathbuf(topic).join("_HEAD");
         if pathbuf.exists() {
-            Ok(Some(fs::read_to_string(pathbuf)?))
+            Ok(Some(fs::read_to_string(&pathbuf).context("read", &pathbuf)?))
         } else {
             Ok(None)
         }
@@ -216,38 +406,66 @@ impl<'a> IOStore for Store<'a> {
     }
 
     fn remove_path(&self, topic: &str, path: &str) -> Result<()> {
-        fs::remove_file(self.get_path_pathbuf(topic, path))?;
+        let pathbuf = self.get_path_pathbuf(topic, path);
+        fs::remove_file(&pathbuf).context("remove", &pathbuf)?;
         Ok(())
     }
 
     fn reset_path(&self, topic: &str, path: &str, uuid: Uuid) -> Result<()> {
-        fs::write(self.get_path_pathbuf(topic, path), uuid.to_string())?;
+        self.write_path(topic, path, uuid)
+    }
+
+    fn rename_path(&self, topic: &str, path: &str, new_path: &str) -> Result<()> {
+        let pathbuf = self.get_path_pathbuf(topic, path);
+        fs::rename(&pathbuf, self.get_path_pathbuf(topic, new_path)).context("rename", &pathbuf)?;
         Ok(())
     }
 
     fn update_note_content(&self, filename: &str, note_id: Uuid) -> Result<()> {
+        let content = fs::read_to_string(filename).context("read", filename)?;
+        self.write_note_content(note_id, &content)
+    }
+
+    fn write_note_content(&self, note_id: Uuid, content: &str) -> Result<()> {
         let target_path = self.get_basedir_pathbuf().join("notes").join(note_id.to_string());
-        fs::copy(filename, target_path)?;
+        fs::write(&target_path, content).context("write", &target_path)?;
+        self.update_search_index(|index| index.index_note(note_id, content))?;
 
         Ok(())
     }
 
     fn get_note_content(&self, uuid: Uuid) -> Result<String> {
         let pathbuf = self.get_basedir_pathbuf().join("notes").join(uuid.to_string());
-        let content =  fs::read_to_string(pathbuf)?;
+        let content = fs::read_to_string(&pathbuf).context("read", &pathbuf)?;
 
         Ok(content)
     }
 
+    fn copy_note_content(&self, from: Uuid, to: Uuid) -> Result<()> {
+        let filename = self.get_basedir_pathbuf().join("notes").join(from.to_string());
+        self.update_note_content(filename.to_str().unwrap_or(""), to)
+    }
+
     fn add_note(&self, topic: &str, path: &str, filename: &str) -> Result<NoteMetaData> {
-        let note_id = Uuid::new_v4();
-        let parent_id = self.get_path(topic, path).ok();
+        self.add_note_with_id(Uuid::new_v4(), topic, path, filename)
+    }
+
+    fn add_note_with_id(&self, note_id: Uuid, topic: &str, path: &str, filename: &str) -> Result<NoteMetaData> {
+        let parents = self.get_path(topic, path).ok().into_iter().collect();
         let metadata = NoteMetaData {
             note_id,
-            parent_id,
+            parents,
             references: Vec::new(),
             topic: topic.to_string(),
             path: path.to_string(),
+            created_at: Some(Utc::now().trunc_subsecs(0)),
+            origin: None,
+            title: None,
+            kind: NoteKind::Permanent,
+            archived: false,
+            source: None,
+            page: None,
+            attachments: Vec::new(),
         };
         self.write_path(topic, path, note_id)?;
         self.write_note_metadata(&metadata)?;
@@ -260,7 +478,8 @@ impl<'a> IOStore for Store<'a> {
         let note_target_path = self.get_basedir_pathbuf()
             .join("meta")
             .join(meta.note_id.to_string());
-        fs::write(&note_target_path, meta.serialize())?;
+        fs::write(&note_target_path, meta.serialize()).context("write", &note_target_path)?;
+        self.reload();
 
         Ok(())
     }
@@ -268,7 +487,7 @@ impl<'a> IOStore for Store<'a> {
     fn get_note_metadata(&self, uuid: Uuid) -> Result<Option<NoteMetaData>> {
         let path = self.get_basedir_pathbuf().join("meta").join(uuid.to_string());
         if path.exists() {
-            let content = fs::read_to_string(&path)?;
+            let content = fs::read_to_string(&path).context("read", &path)?;
             Ok(Some(NoteMetaData::parse_meta_file(uuid, &content)?))
         } else {
             Ok(None)
@@ -276,14 +495,198 @@ impl<'a> IOStore for Store<'a> {
     }
 
     fn search_short_uuid(&self, short_uuid: &str) -> Result<Option<NoteMetaData>> {
-        for entry in fs::read_dir(self.get_basedir_pathbuf().join("meta"))? {
-           let entry = entry?;
-           if &entry.file_name().to_str().unwrap()[..8] == short_uuid {
-                return Ok(self.get_note_metadata(Uuid::parse_str(entry.file_name().to_str().unwrap())?)?)
-           } 
+        let prefix = short_uuid.to_lowercase();
+        if prefix.len() < MIN_SHORT_UUID_LENGTH {
+            return Err(ZtlnError::Default(
+                format!("Short UUID '{}' is too short, at least {} characters are required.", short_uuid, MIN_SHORT_UUID_LENGTH)
+            ));
+        }
+        match self.find_prefix(&prefix)?.as_slice() {
+            [] => Ok(None),
+            [uuid] => self.get_note_metadata(*uuid),
+            candidates => {
+                let mut list = Vec::new();
+                for uuid in candidates {
+                    list.push(self.shortest_unique_prefix(*uuid)?);
+                }
+                Err(ZtlnError::AmbiguousUuid(short_uuid.to_string(), list))
+            },
+        }
+    }
+
+    fn get_notes(&self) -> Result<Vec<Uuid>> {
+        self.with_notes_index(|notes| notes.to_vec())
+    }
+
+    /**
+     * Remove the content and the meta data of a note and forget it in the
+     * tag & search indexes.
+     */
+    fn remove_note(&self, uuid: Uuid) -> Result<()> {
+        let base_dir = self.get_basedir_pathbuf();
+        let meta_path = base_dir.join("meta").join(uuid.to_string());
+        fs::remove_file(&meta_path).context("remove", &meta_path)?;
+        self.reload();
+        let content_path = base_dir.join("notes").join(uuid.to_string());
+        if content_path.exists() {
+            fs::remove_file(&content_path).context("remove", &content_path)?;
+        }
+        self.remove_attachments(uuid)?;
+        let mut index = self.get_index()?;
+        index.retain(|_, list| {
+            list.retain(|note_id| *note_id != uuid);
+            !list.is_empty()
+        });
+        write_binary(&base_dir.join("index"), &index)?;
+        self.update_search_index(|index| index.remove_note(uuid))?;
+
+        Ok(())
+    }
+
+    /**
+     * Like Git, display the shortest prefix that designates only this note,
+     * using at least `SHORT_UUID_LENGTH` characters.
+     */
+    fn shortest_unique_prefix(&self, uuid: Uuid) -> Result<String> {
+        let text = uuid.to_string();
+        let common_length = |other: &Uuid| text.chars()
+            .zip(other.to_string().chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let length = self.with_notes_index(|notes| {
+            let position = notes.partition_point(|other| *other < uuid);
+            let before = if position > 0 { notes.get(position - 1) } else { None };
+            let after = notes[position..].iter().find(|other| **other != uuid);
+            before.into_iter().chain(after).map(common_length).max().unwrap_or(0) + 1
+        })?;
+        let mut length = length.max(SHORT_UUID_LENGTH).min(text.len());
+        if text[..length].ends_with('-') {
+            length += 1;
         }
 
-        Ok(None)
+        Ok(text[..length].to_string())
+    }
+
+    /**
+     * Read a template file, relative paths are relative to the organization
+     * directory.
+     */
+    fn get_template(&self, file: &Path) -> Result<Option<String>> {
+        let pathbuf = self.get_basedir_pathbuf().join(file);
+        if pathbuf.is_file() {
+            Ok(Some(fs::read_to_string(&pathbuf).context("read", &pathbuf)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /**
+     * The sources are kept as BibTeX in the `sources.bib` file.
+     */
+    fn get_sources(&self) -> Result<Vec<Source>> {
+        let pathbuf = self.get_basedir_pathbuf().join("sources.bib");
+        if !pathbuf.is_file() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&pathbuf).context("read", &pathbuf)?;
+
+        Source::parse_bibtex(&content).map_err(|e| ZtlnError::Corrupted(pathbuf, e.to_string()))
+    }
+
+    fn write_sources(&self, sources: &[Source]) -> Result<()> {
+        let pathbuf = self.get_basedir_pathbuf().join("sources.bib");
+        let content = sources.iter().map(|source| source.to_bibtex()).collect::<Vec<String>>().join("\n");
+        fs::write(&pathbuf, content).context("write", &pathbuf)
+    }
+
+    /**
+     * The files attached to a note are kept in `attachments/UUID/`.
+     */
+    fn get_attachment_path(&self, note_id: Uuid, name: &str) -> PathBuf {
+        self.get_basedir_pathbuf().join("attachments").join(note_id.to_string()).join(name)
+    }
+
+    fn write_attachment(&self, note_id: Uuid, name: &str, filename: &Path) -> Result<()> {
+        let pathbuf = self.get_attachment_path(note_id, name);
+        if let Some(directory) = pathbuf.parent() {
+            fs::create_dir_all(directory).context("create directory", directory)?;
+        }
+        fs::copy(filename, &pathbuf).context("copy", filename)?;
+
+        Ok(())
+    }
+
+    fn copy_attachments(&self, from: Uuid, to: Uuid) -> Result<()> {
+        let directory = self.get_basedir_pathbuf().join("attachments").join(from.to_string());
+        if !directory.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(&directory).context("list", &directory)? {
+            let path = entry.context("list", &directory)?.path();
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                self.write_attachment(to, name, &path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * The notes having an attachments directory, whether they exist or not.
+     */
+    fn get_attached_notes(&self) -> Result<Vec<Uuid>> {
+        let directory = self.get_basedir_pathbuf().join("attachments");
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut notes = Vec::new();
+        for entry in fs::read_dir(&directory).context("list", &directory)? {
+            let entry = entry.context("list", &directory)?;
+            if let Some(uuid) = entry.file_name().to_str().and_then(|name| Uuid::parse_str(name).ok()) {
+                notes.push(uuid);
+            }
+        }
+        notes.sort();
+
+        Ok(notes)
+    }
+
+    fn get_attachment_names(&self, note_id: Uuid) -> Result<Vec<String>> {
+        let directory = self.get_basedir_pathbuf().join("attachments").join(note_id.to_string());
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&directory).context("list", &directory)? {
+            let entry = entry.context("list", &directory)?;
+            // names which are not UTF-8 cannot be attached, they are left alone
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn remove_attachment(&self, note_id: Uuid, name: &str) -> Result<()> {
+        let pathbuf = self.get_attachment_path(note_id, name);
+        if pathbuf.is_dir() {
+            fs::remove_dir_all(&pathbuf).context("remove", &pathbuf)?;
+        } else {
+            fs::remove_file(&pathbuf).context("remove", &pathbuf)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_attachments(&self, note_id: Uuid) -> Result<()> {
+        let directory = self.get_basedir_pathbuf().join("attachments").join(note_id.to_string());
+        if directory.exists() {
+            fs::remove_dir_all(&directory).context("remove", &directory)?;
+        }
+
+        Ok(())
     }
 
     fn add_keyword_index(&self, keyword: &str, metadata: &NoteMetaData) -> Result<()> {
@@ -293,8 +696,7 @@ impl<'a> IOStore for Store<'a> {
         } else {
             index.insert(keyword.to_string(), vec![metadata.note_id]);
         }
-        fs::write(self.get_basedir_pathbuf().join("index"), bincode::serialize(&index)?)?;
-        Ok(())
+        write_binary(&self.get_basedir_pathbuf().join("index"), &index)
     }
 
     fn get_meta_from_index(&self, keyword: &str) -> Result<Vec<NoteMetaData>> {
@@ -315,6 +717,44 @@ impl<'a> IOStore for Store<'a> {
         Ok(index.iter().map(|(key, list)| (key.to_owned(), list.len())).collect())
     }
 
+    fn get_note_keywords(&self, uuid: Uuid) -> Result<Vec<String>> {
+        let mut keywords: Vec<String> = self.get_index()?
+            .into_iter()
+            .filter(|(_, list)| list.contains(&uuid))
+            .map(|(keyword, _)| keyword)
+            .collect();
+        keywords.sort();
+
+        Ok(keywords)
+    }
+
+    fn search_notes(&self, query: &str) -> Result<Vec<(Uuid, f64)>> {
+        if let Some(index) = self.search_index.borrow().as_ref() {
+            return Ok(index.search(query));
+        }
+
+        Ok(self.get_search_index()?.search(query))
+    }
+
+}
+
+/**
+Load one of the indexes, a file which cannot be decoded is reported as
+corrupted.
+ */
+fn read_binary<T: DeserializeOwned>(pathbuf: &Path) -> Result<T> {
+    let bytes = fs::read(pathbuf).context("read", pathbuf)?;
+
+    bincode::deserialize(bytes.as_slice())
+        .map_err(|e| ZtlnError::Corrupted(pathbuf.to_path_buf(), e.to_string()))
+}
+
+fn write_binary<T: Serialize>(pathbuf: &Path, value: &T) -> Result<()> {
+    let bytes = bincode::serialize(value)
+        .map_err(|e| ZtlnError::Corrupted(pathbuf.to_path_buf(), e.to_string()))?;
+    fs::write(pathbuf, bytes).context("write", pathbuf)?;
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -331,6 +771,7 @@ mod tests {
         assert!(path.join("meta").is_dir());
         assert!(path.join("notes").is_dir());
         assert!(path.join("index").is_file());
+        assert!(path.join("search_index").is_file());
 
         fs::remove_dir_all(path).unwrap();
     }
@@ -389,13 +830,13 @@ mod tests {
         let result = store.add_note("topicA", "main", "tmp/test5");
         assert!(result.is_ok(), "adding a note returns OK");
         let note = result.unwrap();
-        assert!(note.parent_id.is_none(), "when a topic is new, there is no parent_id");
+        assert!(note.parents.is_empty(), "when a topic is new, there is no parent");
         assert_eq!(note.note_id.to_string(), fs::read_to_string(base_dir_path.join("topics/topicA/paths/main")).unwrap(), "path has been updated");
         assert!(base_dir_path.join("meta").join(note.note_id.to_string()).is_file(), "meta file exists");
         assert_eq!("This is a note", fs::read_to_string(base_dir_path.join("notes").join(note.note_id.to_string())).unwrap(), "content file is up to date");
         fs::write(draft_note_path, "This is another note").unwrap();
         let another_note = store.add_note("topicA", "main", "tmp/test5").unwrap();
-        assert_eq!(Some(note.note_id), another_note.parent_id, "new note relates to parent");
+        assert_eq!(Some(note.note_id), another_note.parent_id(), "new note relates to parent");
         assert_eq!(another_note.note_id.to_string(), fs::read_to_string(base_dir_path.join("topics/topicA/paths/main")).unwrap(), "path has been updated");
 
         fs::remove_dir_all(base_dir).unwrap();
@@ -414,7 +855,7 @@ mod tests {
         if res.is_err() {
             println!("got error: {:?}", res);
         }
-        assert!(res.is_ok(), format!("note '{}' is fetched", metadata.note_id));
+        assert!(res.is_ok(), "note '{}' is fetched", metadata.note_id);
         let some_meta = res.unwrap();
         assert!(some_meta.is_some());
         let note_meta = some_meta.unwrap();
@@ -493,4 +934,179 @@ mod tests {
         assert_eq!(metadata1.note_id, uuid);
         fs::remove_dir_all(base_dir).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn search_notes() {
+        let base_dir = "tmp/ztln_store10";
+        let store = Store::init(base_dir).unwrap();
+        let topic = "topicA";
+        store.create_topic(topic).unwrap();
+        let draft_note_path = Path::new("tmp/test_store10");
+        fs::write(draft_note_path, "The borrow checker").unwrap();
+        let metadata1 = store.add_note(topic, "main", "tmp/test_store10").unwrap();
+        fs::write(draft_note_path, "Ownership rules").unwrap();
+        let metadata2 = store.add_note(topic, "main", "tmp/test_store10").unwrap();
+        let results = store.search_notes("ownership").unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(metadata2.note_id, results[0].0);
+        fs::write(draft_note_path, "Ownership and borrowing").unwrap();
+        store.update_note_content("tmp/test_store10", metadata1.note_id).unwrap();
+        assert_eq!(2, store.search_notes("ownership").unwrap().len(), "updated content is indexed");
+        fs::remove_file(Path::new(base_dir).join("search_index")).unwrap();
+        assert_eq!(2, store.search_notes("ownership").unwrap().len(), "missing index is rebuilt");
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn search_short_uuid() {
+        let base_dir = "tmp/ztln_store11";
+        let store = Store::init(base_dir).unwrap();
+        let topic = "topicA";
+        store.create_topic(topic).unwrap();
+        let draft_note_path = Path::new("tmp/test_store11");
+        fs::write(draft_note_path, "This is a test 11 note").unwrap();
+        let metadata = store.add_note(topic, "main", "tmp/test_store11").unwrap();
+        let text = metadata.note_id.to_string();
+        assert_eq!(metadata, store.search_short_uuid(&text[..4]).unwrap().unwrap(), "4 characters prefix");
+        assert_eq!(metadata, store.search_short_uuid(&text[..11].to_uppercase()).unwrap().unwrap(), "case insensitive");
+        assert!(store.search_short_uuid(&text[..3]).is_err(), "prefix is too short");
+        assert_eq!(text[..8], store.shortest_unique_prefix(metadata.note_id).unwrap());
+        let other = if text.starts_with('0') { "1" } else { "0" };
+        assert!(store.search_short_uuid(&format!("{}000", other)).unwrap().is_none());
+
+        // forge notes sharing the same 11 first characters
+        let uuid1 = Uuid::parse_str("abcdef01-2345-4000-8000-000000000001").unwrap();
+        let uuid2 = Uuid::parse_str("abcdef01-2399-4000-8000-000000000002").unwrap();
+        for uuid in &[uuid1, uuid2] {
+            store.write_note_metadata(&NoteMetaData::parse_meta_file(*uuid, "\ntopicA\nmain").unwrap()).unwrap();
+        }
+        assert_eq!(3, store.get_notes().unwrap().len());
+        match store.search_short_uuid("abcdef") {
+            Err(e) => assert!(e.to_string().contains("abcdef01-234, abcdef01-239"), "ambiguity lists the candidates: {}", e),
+            Ok(meta) => panic!("ambiguous prefix must fail, got {:?}", meta),
+        }
+        assert_eq!(uuid2, store.search_short_uuid("abcdef01-239").unwrap().unwrap().note_id);
+        assert_eq!("abcdef01-234", store.shortest_unique_prefix(uuid1).unwrap());
+        fs::write(Path::new(base_dir).join("meta").join("short"), "").unwrap();
+        assert_eq!(3, Store::attach(base_dir).unwrap().get_notes().unwrap().len(), "non UUID files are ignored");
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn topic_management() {
+        let base_dir = "tmp/ztln_store12";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        store.set_current_topic("topicA").unwrap();
+        assert!(!store.is_topic_archived("topicA"));
+        store.set_topic_archived("topicA", true).unwrap();
+        assert!(store.is_topic_archived("topicA"));
+        store.set_topic_archived("topicA", false).unwrap();
+        assert!(!store.is_topic_archived("topicA"));
+        store.rename_topic("topicA", "topicB").unwrap();
+        assert_eq!(vec!["topicB"], store.get_topics().unwrap());
+        store.remove_topic("topicB").unwrap();
+        assert!(store.get_topics().unwrap().is_empty());
+        store.unset_current_topic().unwrap();
+        assert!(store.get_current_topic().unwrap().is_none());
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn remove_note() {
+        let base_dir = "tmp/ztln_store13";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        let draft_note_path = Path::new("tmp/test_store13");
+        fs::write(draft_note_path, "This is a test 13 note").unwrap();
+        let metadata = store.add_note("topicA", "main", "tmp/test_store13").unwrap();
+        store.add_keyword_index("keyword", &metadata).unwrap();
+        store.write_attachment(metadata.note_id, "draft.txt", draft_note_path).unwrap();
+        assert!(store.get_attachment_path(metadata.note_id, "draft.txt").is_file());
+        store.remove_note(metadata.note_id).unwrap();
+        assert!(!store.get_attachment_path(metadata.note_id, "draft.txt").exists());
+        assert!(store.get_note_metadata(metadata.note_id).unwrap().is_none());
+        assert!(store.get_notes().unwrap().is_empty());
+        assert!(store.get_keywords().unwrap().is_empty());
+        assert!(store.search_notes("test").unwrap().is_empty());
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn topic_description() {
+        let base_dir = "tmp/ztln_store14";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        assert_eq!(None, store.get_topic_description("topicA").unwrap());
+        store.set_topic_description("topicA", Some("About A.\nMore details.")).unwrap();
+        assert_eq!(Some("About A.\nMore details.".to_string()), store.get_topic_description("topicA").unwrap());
+        store.set_topic_description("topicA", None).unwrap();
+        assert_eq!(None, store.get_topic_description("topicA").unwrap());
+        store.set_topic_description("topicA", None).unwrap();
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn copy_note_content() {
+        let base_dir = "tmp/ztln_store15";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        let draft_note_path = Path::new("tmp/test_store15");
+        fs::write(draft_note_path, "This is a test 15 note").unwrap();
+        let metadata = store.add_note("topicA", "main", "tmp/test_store15").unwrap();
+        store.add_keyword_index("tag2", &metadata).unwrap();
+        store.add_keyword_index("tag1", &metadata).unwrap();
+        let copy_id = Uuid::new_v4();
+        store.copy_note_content(metadata.note_id, copy_id).unwrap();
+        assert_eq!("This is a test 15 note", store.get_note_content(copy_id).unwrap());
+        assert_eq!(2, store.search_notes("note").unwrap().len(), "the copy is indexed");
+        assert_eq!(vec!["tag1", "tag2"], store.get_note_keywords(metadata.note_id).unwrap());
+        assert!(store.get_note_keywords(copy_id).unwrap().is_empty());
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn notes_cache() {
+        let base_dir = "tmp/ztln_store16";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        fs::write("tmp/test_store16", "This is a test 16 note").unwrap();
+        let metadata = store.add_note("topicA", "main", "tmp/test_store16").unwrap();
+        assert_eq!(vec![metadata.note_id], store.get_notes().unwrap());
+        let other = Store::attach(base_dir).unwrap();
+        let added = other.add_note("topicA", "main", "tmp/test_store16").unwrap();
+        store.reload();
+        assert_eq!(2, store.get_notes().unwrap().len(), "notes added by another store are seen");
+        other.remove_note(added.note_id).unwrap();
+        store.reload();
+        assert_eq!(vec![metadata.note_id], store.get_notes().unwrap());
+        store.remove_note(metadata.note_id).unwrap();
+        assert!(store.get_notes().unwrap().is_empty(), "writes forget the cached notes");
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn hold_search_index() {
+        let base_dir = "tmp/ztln_store17";
+        let store = Store::init(base_dir).unwrap();
+        store.create_topic("topicA").unwrap();
+        fs::write("tmp/test_store17", "Ownership rules").unwrap();
+        let metadata = store.add_note("topicA", "main", "tmp/test_store17").unwrap();
+        let other = Store::attach(base_dir).unwrap();
+        store.hold_search_index().unwrap();
+        let added = store.add_note("topicA", "main", "tmp/test_store17").unwrap();
+        store.remove_note(metadata.note_id).unwrap();
+        assert_eq!(vec![added.note_id], store.search_notes("ownership").unwrap().into_iter().map(|(uuid, _)| uuid).collect::<Vec<_>>());
+        assert_eq!(vec![metadata.note_id], other.search_notes("ownership").unwrap().into_iter().map(|(uuid, _)| uuid).collect::<Vec<_>>(), "the file is not written yet");
+        store.release_search_index().unwrap();
+        assert_eq!(vec![added.note_id], other.search_notes("ownership").unwrap().into_iter().map(|(uuid, _)| uuid).collect::<Vec<_>>(), "the file is written once released");
+
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+}
@@ -0,0 +1,821 @@
+use crate::error::*;
+use crate::note::NoteMetaData;
+use crate::organization::Organization;
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use std::io::stdout;
+
+const HELP: &str = "q quit  tab focus  ↑↓ move  enter open  ⌫ back  a add  e edit  t tag  r reference  g go to  F5 reload";
+
+/**
+ * Panes of the interface, in focus order.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pane {
+    Sidebar,
+    Tags,
+    History,
+    Note,
+}
+
+/**
+ * An entry of the sidebar: a topic followed by its paths.
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum SidebarEntry {
+    Topic(String),
+    Path { topic: String, path: String },
+}
+
+/**
+ * The text prompted on the status line and what it is used for.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prompt {
+    Tag,
+    Reference,
+    Goto,
+}
+
+/**
+ * The operations needing the terminal back to launch the editor.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditorRequest {
+    Add,
+    Edit,
+}
+
+/**
+ * A note listed in the history pane or in the links of the note viewer.
+ */
+#[derive(Debug)]
+struct Entry {
+    metadata: NoteMetaData,
+    label: String,
+}
+
+/**
+ * State of the terminal interface: a topic and path sidebar with the tags
+ * below it, the history of the selected path (or the notes of the selected
+ * tag) and a viewer showing the selected note with its references and
+ * backlinks.
+ */
+pub struct App<'a> {
+    orga: Organization<'a>,
+    focus: Pane,
+    sidebar: Vec<SidebarEntry>,
+    sidebar_state: ListState,
+    tags: Vec<(String, usize)>,
+    tags_state: ListState,
+    history_title: String,
+    history: Vec<Entry>,
+    history_state: ListState,
+    note: Option<NoteMetaData>,
+    content: String,
+    scroll: u16,
+    links: Vec<Entry>,
+    links_state: ListState,
+    trail: Vec<NoteMetaData>,
+    prompt: Option<(Prompt, String)>,
+    message: String,
+    areas: Vec<(Pane, Rect)>,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    /**
+     * Load the topics and tags of the organization and select the current
+     * path.
+     */
+    pub fn new(orga: Organization<'a>) -> Result<Self> {
+        let mut app = App {
+            orga,
+            focus: Pane::Sidebar,
+            sidebar: Vec::new(),
+            sidebar_state: ListState::default(),
+            tags: Vec::new(),
+            tags_state: ListState::default(),
+            history_title: String::new(),
+            history: Vec::new(),
+            history_state: ListState::default(),
+            note: None,
+            content: String::new(),
+            scroll: 0,
+            links: Vec::new(),
+            links_state: ListState::default(),
+            trail: Vec::new(),
+            prompt: None,
+            message: HELP.to_string(),
+            areas: Vec::new(),
+            quit: false,
+        };
+        app.refresh()?;
+        let current = match app.orga.get_current_topic()? {
+            Some(topic) => app.orga.get_current_path(&topic)?.map(|path| SidebarEntry::Path { topic, path }),
+            None => None,
+        };
+        let position = current.and_then(|entry| app.sidebar.iter().position(|e| *e == entry));
+        app.sidebar_state.select(position.or(if app.sidebar.is_empty() { None } else { Some(0) }));
+        app.load_sidebar_selection()?;
+
+        Ok(app)
+    }
+
+    /**
+     * Run the interface until the user quits. The editor function receives
+     * the initial content and returns the name of the edited file.
+     */
+    pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>, editor: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if let Some(request) = self.handle_key(key) {
+                        leave_terminal()?;
+                        let result = self.launch_editor(request, editor);
+                        enter_terminal()?;
+                        terminal.clear()?;
+                        self.report(result);
+                    }
+                },
+                Event::Mouse(mouse) => self.handle_mouse(mouse),
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Reload the sidebar and the tags, keeping the selections in range.
+     */
+    fn refresh(&mut self) -> Result<()> {
+        self.sidebar.clear();
+        for topic in self.orga.get_topics_list(false)? {
+            let (_, paths) = self.orga.get_paths_list(Some(&topic))?;
+            self.sidebar.push(SidebarEntry::Topic(topic.clone()));
+            for path in paths {
+                self.sidebar.push(SidebarEntry::Path { topic: topic.clone(), path });
+            }
+        }
+        self.tags = self.orga.list_keywords()?;
+        clamp(&mut self.sidebar_state, self.sidebar.len());
+        clamp(&mut self.tags_state, self.tags.len());
+
+        Ok(())
+    }
+
+    /**
+     * Read the organization again to show the changes made by other
+     * processes, keeping the selections.
+     */
+    fn reload(&mut self) -> Result<()> {
+        let selected = self.history_state.selected();
+        self.orga.reload();
+        self.refresh()?;
+        if self.history_title.starts_with('#') {
+            self.load_tags_selection()?;
+        } else {
+            self.load_sidebar_selection()?;
+        }
+        if let Some(selected) = selected.filter(|selected| *selected < self.history.len()) {
+            self.history_state.select(Some(selected));
+            self.load_history_selection()?;
+        }
+        self.message = "Organization reloaded.".to_string();
+
+        Ok(())
+    }
+
+    /**
+     * Show the history of the selected path, or of the current path of the
+     * selected topic.
+     */
+    fn load_sidebar_selection(&mut self) -> Result<()> {
+        let (topic, path) = match self.sidebar_state.selected().and_then(|i| self.sidebar.get(i)) {
+            Some(SidebarEntry::Path { topic, path }) => (topic.clone(), Some(path.clone())),
+            Some(SidebarEntry::Topic(topic)) => (topic.clone(), self.orga.get_current_path(topic)?),
+            None => return self.load_history(String::new(), Vec::new()),
+        };
+        match path {
+            Some(path) => {
+                let notes = self.orga.get_path_log(Some(&path), Some(&topic))?;
+                self.load_history(format!("{}/{}", topic, path), notes)
+            },
+            None => self.load_history(topic, Vec::new()),
+        }
+    }
+
+    /**
+     * Show the notes tagged with the selected keyword.
+     */
+    fn load_tags_selection(&mut self) -> Result<()> {
+        match self.tags_state.selected().and_then(|i| self.tags.get(i)) {
+            Some((keyword, _)) => {
+                let title = format!("#{}", keyword);
+                let notes = self.orga.search_keyword(keyword)?;
+                self.load_history(title, notes)
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn load_history(&mut self, title: String, notes: Vec<NoteMetaData>) -> Result<()> {
+        self.history_title = title;
+        self.history.clear();
+        for metadata in notes {
+            let label = self.describe(&metadata)?;
+            self.history.push(Entry { metadata, label });
+        }
+        self.history_state.select(if self.history.is_empty() { None } else { Some(0) });
+        self.trail.clear();
+        self.load_history_selection()
+    }
+
+    fn load_history_selection(&mut self) -> Result<()> {
+        match self.history_state.selected().and_then(|i| self.history.get(i)) {
+            Some(entry) => {
+                let location = entry.metadata.note_id.to_string();
+                self.open_note(&location)
+            },
+            None => {
+                self.note = None;
+                self.content.clear();
+                self.links.clear();
+                self.links_state.select(None);
+                Ok(())
+            },
+        }
+    }
+
+    /**
+     * Show the note at the given location with the notes it references and
+     * the notes referencing it.
+     */
+    fn open_note(&mut self, location: &str) -> Result<()> {
+        let metadata = self.orga.solve_location(location)?
+            .ok_or_else(|| ZtlnError::LocationError(location.to_string()))?;
+        self.content = self.orga.get_note_content(metadata.note_id)?;
+        self.scroll = 0;
+        self.links.clear();
+        for reference in &metadata.references {
+            if let Some(target) = self.orga.solve_location(&reference.to_string())? {
+                let label = format!("→ {}", self.describe(&target)?);
+                self.links.push(Entry { metadata: target, label });
+            }
+        }
+        for source in self.orga.get_backlinks(&metadata)? {
+            let label = format!("← {}", self.describe(&source)?);
+            self.links.push(Entry { metadata: source, label });
+        }
+        self.links_state.select(if self.links.is_empty() { None } else { Some(0) });
+        self.note = Some(metadata);
+
+        Ok(())
+    }
+
+    /**
+     * Open the selected link.
+     */
+    fn follow_link(&mut self) -> Result<()> {
+        let location = match self.links_state.selected().and_then(|i| self.links.get(i)) {
+            Some(entry) => entry.metadata.note_id.to_string(),
+            None => return Ok(()),
+        };
+        self.visit(&location)
+    }
+
+    /**
+     * Open the note at the given location, keeping the current note in the
+     * trail. The current note stays displayed if the location is wrong.
+     */
+    fn visit(&mut self, location: &str) -> Result<()> {
+        let previous = self.note.take();
+        if let Err(e) = self.open_note(location) {
+            self.note = previous;
+            return Err(e);
+        }
+        self.trail.extend(previous);
+
+        Ok(())
+    }
+
+    fn go_back(&mut self) -> Result<()> {
+        match self.trail.pop() {
+            Some(metadata) => self.open_note(&metadata.note_id.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * One line summary of a note: short UUID, creation date and title.
+     */
+    fn describe(&self, metadata: &NoteMetaData) -> Result<String> {
+        Ok(format!(
+            "{} {} {}",
+            self.orga.short_uuid(metadata.note_id)?,
+            metadata.created_at.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            self.orga.get_title(metadata)?
+        ))
+    }
+
+    /**
+     * The topic and path where a new note is added: the selected path or the
+     * current path of the selected topic.
+     */
+    fn target(&mut self) -> Option<(String, Option<String>)> {
+        match self.sidebar_state.selected().and_then(|i| self.sidebar.get(i)) {
+            Some(SidebarEntry::Path { topic, path }) => Some((topic.clone(), Some(path.clone()))),
+            Some(SidebarEntry::Topic(topic)) => Some((topic.clone(), None)),
+            None => None,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<EditorRequest> {
+        if let Some((prompt, mut input)) = self.prompt.take() {
+            match key.code {
+                KeyCode::Enter => {
+                    let result = self.submit(prompt, input.trim());
+                    self.report(result);
+                },
+                KeyCode::Esc => self.message = HELP.to_string(),
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.prompt = Some((prompt, input));
+                },
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.prompt = Some((prompt, input));
+                },
+                _ => self.prompt = Some((prompt, input)),
+            }
+            return None;
+        }
+        let result = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.quit = true;
+                Ok(())
+            },
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Pane::Sidebar => Pane::Tags,
+                    Pane::Tags => Pane::History,
+                    Pane::History => Pane::Note,
+                    Pane::Note => Pane::Sidebar,
+                };
+                Ok(())
+            },
+            KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Pane::Sidebar => Pane::Note,
+                    Pane::Tags => Pane::Sidebar,
+                    Pane::History => Pane::Tags,
+                    Pane::Note => Pane::History,
+                };
+                Ok(())
+            },
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => {
+                self.scroll = self.scroll.saturating_sub(10);
+                Ok(())
+            },
+            KeyCode::PageDown => {
+                self.scroll = self.scroll.saturating_add(10);
+                Ok(())
+            },
+            KeyCode::Enter => match self.focus {
+                Pane::Sidebar | Pane::Tags => {
+                    self.focus = Pane::History;
+                    Ok(())
+                },
+                Pane::History => {
+                    self.focus = Pane::Note;
+                    Ok(())
+                },
+                Pane::Note => self.follow_link(),
+            },
+            KeyCode::Backspace => self.go_back(),
+            KeyCode::Char('a') => return Some(EditorRequest::Add),
+            KeyCode::Char('e') if self.note.is_some() => return Some(EditorRequest::Edit),
+            KeyCode::Char('t') if self.note.is_some() => self.ask(Prompt::Tag),
+            KeyCode::Char('r') if self.note.is_some() => self.ask(Prompt::Reference),
+            KeyCode::Char('g') => self.ask(Prompt::Goto),
+            KeyCode::F(5) => self.reload(),
+            _ => Ok(()),
+        };
+        self.report(result);
+
+        None
+    }
+
+    /**
+     * A click selects the clicked row of a list and focuses its pane, a click
+     * on a link follows it.
+     */
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) || self.prompt.is_some() {
+            return;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        let clicked = self.areas.iter().find(|(_, area)| area.contains(position)).copied();
+        if let Some((pane, area)) = clicked {
+            self.focus = pane;
+            if mouse.row <= area.y || mouse.row + 1 >= area.bottom() {
+                return;
+            }
+            let (state, len) = match pane {
+                Pane::Sidebar => (&self.sidebar_state, self.sidebar.len()),
+                Pane::Tags => (&self.tags_state, self.tags.len()),
+                Pane::History => (&self.history_state, self.history.len()),
+                Pane::Note => (&self.links_state, self.links.len()),
+            };
+            let row = state.offset() + (mouse.row - area.y - 1) as usize;
+            if row < len {
+                let selected = state.selected().unwrap_or(0);
+                let result = self.move_selection(row as isize - selected as isize)
+                    .and_then(|_| if pane == Pane::Note { self.follow_link() } else { Ok(()) });
+                self.report(result);
+            }
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) -> Result<()> {
+        match self.focus {
+            Pane::Sidebar => if shift(&mut self.sidebar_state, self.sidebar.len(), delta) {
+                return self.load_sidebar_selection();
+            },
+            Pane::Tags => if shift(&mut self.tags_state, self.tags.len(), delta) {
+                return self.load_tags_selection();
+            },
+            Pane::History => if shift(&mut self.history_state, self.history.len(), delta) {
+                return self.load_history_selection();
+            },
+            Pane::Note => {
+                shift(&mut self.links_state, self.links.len(), delta);
+            },
+        }
+
+        Ok(())
+    }
+
+    fn ask(&mut self, prompt: Prompt) -> Result<()> {
+        self.prompt = Some((prompt, String::new()));
+        Ok(())
+    }
+
+    fn submit(&mut self, prompt: Prompt, input: &str) -> Result<()> {
+        if input.is_empty() {
+            return Ok(());
+        }
+        let location = self.note.as_ref().map(|metadata| metadata.note_id.to_string()).unwrap_or_default();
+        match prompt {
+            Prompt::Tag => {
+                self.orga.add_keyword(input, Some(&location))?;
+                self.refresh()?;
+                self.message = format!("Note tagged with '{}'.", input);
+            },
+            Prompt::Reference => {
+                self.orga.add_note_reference(&location, input)?;
+                self.open_note(&location)?;
+                self.message = format!("Reference to '{}' added.", input);
+            },
+            Prompt::Goto => {
+                self.visit(input)?;
+                self.focus = Pane::Note;
+                self.message = HELP.to_string();
+            },
+        }
+
+        Ok(())
+    }
+
+    fn launch_editor(&mut self, request: EditorRequest, editor: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        let content = match request {
+            EditorRequest::Add => String::new(),
+            EditorRequest::Edit => self.content.clone(),
+        };
+        let filename = editor(&content)?;
+        let result = self.apply_editor(request, &filename);
+        if std::path::Path::new(&filename).exists() {
+            std::fs::remove_file(&filename)?;
+        }
+
+        result
+    }
+
+    /**
+     * Add the file as a new note on the selected path, or use it as the new
+     * content of the displayed note.
+     */
+    fn apply_editor(&mut self, request: EditorRequest, filename: &str) -> Result<()> {
+        match request {
+            EditorRequest::Add => {
+                let (topic, path) = self.target()
+                    .ok_or_else(|| ZtlnError::Default("No topic selected.".to_string()))?;
+                let metadata = self.orga.add_note(filename, Some(&topic), path.as_deref())?;
+                self.refresh()?;
+                let entry = SidebarEntry::Path { topic: metadata.topic.clone(), path: metadata.path.clone() };
+                if let Some(position) = self.sidebar.iter().position(|e| *e == entry) {
+                    self.sidebar_state.select(Some(position));
+                }
+                self.load_sidebar_selection()?;
+                self.message = format!("Note '{}' added at {}/{}.", self.orga.short_uuid(metadata.note_id)?, metadata.topic, metadata.path);
+            },
+            EditorRequest::Edit => {
+                let location = self.note.as_ref().map(|metadata| metadata.note_id.to_string()).unwrap_or_default();
+                let metadata = self.orga.edit_note(&location, filename)?;
+                self.open_note(&location)?;
+                if let Some(index) = self.history.iter().position(|entry| entry.metadata.note_id == metadata.note_id) {
+                    self.history[index].label = self.describe(&metadata)?;
+                    self.history[index].metadata = metadata;
+                }
+                self.message = "Note updated.".to_string();
+            },
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Errors are displayed on the status line instead of leaving the
+     * interface.
+     */
+    fn report(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.message = format!("ERROR: {}", e);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, middle, right] = Layout::horizontal([
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+            Constraint::Percentage(40),
+        ]).areas(main);
+        let [sidebar_area, tags_area] = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(left);
+        let links_height = (self.links.len() as u16 + 2).clamp(3, 10);
+        let [note_area, links_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(links_height)]).areas(right);
+        self.areas = vec![
+            (Pane::Sidebar, sidebar_area),
+            (Pane::Tags, tags_area),
+            (Pane::History, middle),
+            (Pane::Note, links_area),
+        ];
+
+        let current_topic = self.orga.get_current_topic().ok().flatten();
+        let mut current_path = None;
+        if let Some(topic) = current_topic.as_ref() {
+            current_path = self.orga.get_current_path(topic).ok().flatten();
+        }
+        let sidebar: Vec<ListItem> = self.sidebar.iter()
+            .map(|entry| match entry {
+                SidebarEntry::Topic(topic) if Some(topic) == current_topic.as_ref() => ListItem::new(format!("{} *", topic)).bold(),
+                SidebarEntry::Topic(topic) => ListItem::new(topic.to_string()).bold(),
+                SidebarEntry::Path { topic, path } if Some(topic) == current_topic.as_ref() && Some(path) == current_path.as_ref()
+                    => ListItem::new(format!("  {} *", path)),
+                SidebarEntry::Path { path, .. } => ListItem::new(format!("  {}", path)),
+            })
+            .collect();
+        frame.render_stateful_widget(self.list(sidebar, "Topics", Pane::Sidebar), sidebar_area, &mut self.sidebar_state);
+        let tags: Vec<ListItem> = self.tags.iter()
+            .map(|(keyword, count)| ListItem::new(format!("{} ({})", keyword, count)))
+            .collect();
+        frame.render_stateful_widget(self.list(tags, "Tags", Pane::Tags), tags_area, &mut self.tags_state);
+        let history: Vec<ListItem> = self.history.iter().map(|entry| ListItem::new(entry.label.clone())).collect();
+        let title = format!("History {}", self.history_title);
+        frame.render_stateful_widget(self.list(history, &title, Pane::History), middle, &mut self.history_state);
+
+        let note_title = match self.note.as_ref() {
+            Some(metadata) => format!("Note {}", self.orga.locate_note(metadata).ok().flatten()
+                .or_else(|| self.orga.short_uuid(metadata.note_id).ok())
+                .unwrap_or_default()),
+            None => "Note".to_string(),
+        };
+        let note = Paragraph::new(self.content.as_str())
+            .block(self.block(&note_title, Pane::Note))
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll, 0));
+        frame.render_widget(note, note_area);
+        let links: Vec<ListItem> = self.links.iter().map(|entry| ListItem::new(entry.label.clone())).collect();
+        frame.render_stateful_widget(self.list(links, "Links", Pane::Note), links_area, &mut self.links_state);
+
+        let status_line = match self.prompt.as_ref() {
+            Some((Prompt::Tag, input)) => format!("Tag: {}", input),
+            Some((Prompt::Reference, input)) => format!("Reference to: {}", input),
+            Some((Prompt::Goto, input)) => format!("Go to: {}", input),
+            None => self.message.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+
+    fn block(&self, title: &str, pane: Pane) -> Block<'static> {
+        let block = Block::bordered().title(title.to_string());
+        if self.focus == pane {
+            block.border_style(Style::new().yellow())
+        } else {
+            block
+        }
+    }
+
+    fn list(&self, items: Vec<ListItem<'static>>, title: &str, pane: Pane) -> List<'static> {
+        List::new(items)
+            .block(self.block(title, pane))
+            .highlight_style(Style::new().reversed())
+    }
+}
+
+/**
+ * Open the organization in the terminal interface.
+ */
+pub fn tui(orga: Organization, editor: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+    let mut app = App::new(orga)?;
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    app.run(&mut terminal, editor)
+}
+
+/**
+ * Puts the terminal back in its normal mode when dropped, even when the
+ * interface returns early with an error. A panic restores it before its
+ * message is printed.
+ */
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<Self> {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = leave_terminal();
+            hook(info);
+        }));
+        enter_terminal()?;
+
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = leave_terminal();
+    }
+}
+
+fn enter_terminal() -> Result<()> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(())
+}
+
+fn leave_terminal() -> Result<()> {
+    execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    Ok(())
+}
+
+/**
+ * Move the selection of a list, return whether it changed.
+ */
+fn shift(state: &mut ListState, len: usize, delta: isize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    let selected = state.selected().unwrap_or(0);
+    let target = (selected as isize + delta).clamp(0, len as isize - 1) as usize;
+    state.select(Some(target));
+
+    target != selected
+}
+
+fn clamp(state: &mut ListState, len: usize) {
+    match state.selected() {
+        _ if len == 0 => state.select(None),
+        Some(selected) if selected >= len => state.select(Some(len - 1)),
+        None => state.select(Some(0)),
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+    use crossterm::event::KeyModifiers;
+    use ratatui::backend::TestBackend;
+
+    fn press(app: &mut App, code: KeyCode) -> Option<EditorRequest> {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+        press(app, KeyCode::Enter);
+    }
+
+    #[test]
+    fn browse() {
+        let base_dir = "tmp/ztln_tui1";
+        let filename = "tmp/test_tui1";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "First note").unwrap();
+        let meta1 = orga.add_note(filename, None, None).unwrap();
+        std::fs::write(filename, "Second note").unwrap();
+        let meta2 = orga.add_note(filename, None, None).unwrap();
+        orga.add_note_reference("main", "main~1").unwrap();
+        let mut app = App::new(orga).unwrap();
+        assert_eq!(vec![
+            SidebarEntry::Topic("topic1".to_string()),
+            SidebarEntry::Path { topic: "topic1".to_string(), path: "main".to_string() },
+        ], app.sidebar);
+        assert_eq!(Some(1), app.sidebar_state.selected());
+        assert_eq!("topic1/main", app.history_title);
+        assert_eq!(2, app.history.len());
+        assert_eq!(Some(meta2.note_id), app.note.as_ref().map(|m| m.note_id));
+        assert_eq!("Second note", app.content);
+        assert_eq!(vec![meta1.note_id], app.links.iter().map(|e| e.metadata.note_id).collect::<Vec<_>>());
+        assert!(app.links[0].label.starts_with("→ "));
+
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(Pane::History, app.focus);
+        press(&mut app, KeyCode::Down);
+        assert_eq!("First note", app.content);
+        assert!(app.links[0].label.starts_with("← "), "backlinks are listed");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!("Second note", app.content, "the backlink is followed");
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!("First note", app.content);
+
+        press(&mut app, KeyCode::Char('t'));
+        type_text(&mut app, "rust");
+        assert_eq!(vec![("rust".to_string(), 1)], app.tags);
+        press(&mut app, KeyCode::Char('g'));
+        type_text(&mut app, "wrong");
+        assert!(app.message.starts_with("ERROR"));
+        press(&mut app, KeyCode::Char('g'));
+        type_text(&mut app, "#rust");
+        assert_eq!("First note", app.content);
+
+        assert_eq!(Some(EditorRequest::Edit), press(&mut app, KeyCode::Char('e')));
+        std::fs::write(filename, "First note, reworked").unwrap();
+        app.apply_editor(EditorRequest::Edit, filename).unwrap();
+        assert_eq!("First note, reworked", app.content);
+        assert!(app.history[1].label.ends_with("First note, reworked"));
+        assert_eq!(Some(EditorRequest::Add), press(&mut app, KeyCode::Char('a')));
+        std::fs::write(filename, "Third note").unwrap();
+        app.apply_editor(EditorRequest::Add, filename).unwrap();
+        assert_eq!(3, app.history.len());
+        assert_eq!("Third note", app.content);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("topic1 *"));
+        assert!(screen.contains("rust (1)"));
+        assert!(screen.contains("Third note"));
+        assert!(screen.contains("History topic1/main"));
+
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.quit);
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+
+    #[test]
+    fn reload() {
+        let base_dir = "tmp/ztln_tui2";
+        let filename = "tmp/test_tui2";
+        let mut orga = Organization::new(Store::init(base_dir).unwrap());
+        orga.create_topic("topic1").unwrap();
+        std::fs::write(filename, "First note").unwrap();
+        orga.add_note(filename, None, None).unwrap();
+        let mut app = App::new(orga).unwrap();
+        assert_eq!(1, app.history.len());
+
+        let mut other = Organization::new(Store::attach(base_dir).unwrap());
+        std::fs::write(filename, "Second note").unwrap();
+        let meta2 = other.add_note(filename, None, None).unwrap();
+        other.create_topic("topic2").unwrap();
+        other.add_keyword("rust", None).unwrap();
+        press(&mut app, KeyCode::F(5));
+        assert_eq!(2, app.history.len(), "notes added by another process are listed");
+        assert_eq!(Some(meta2.note_id), app.note.as_ref().map(|m| m.note_id));
+        assert_eq!(vec![("rust".to_string(), 1)], app.tags);
+        assert!(app.sidebar.contains(&SidebarEntry::Topic("topic2".to_string())));
+        assert_eq!("Organization reloaded.", app.message);
+
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+}